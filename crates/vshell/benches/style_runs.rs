@@ -0,0 +1,55 @@
+#![feature(test)]
+
+extern crate test;
+
+use test::{black_box, Bencher};
+use vshell::{Grid, Term};
+
+/// A full screen of text changing colour every few characters, like the
+/// output of `ls --color` or a syntax highlighted file.
+fn colored(columns: usize, lines: usize) -> Term {
+    let mut term = Term::new(columns, lines);
+    for index in 0 .. columns * lines / 8 {
+        term.advance(format!("\x1b[{}mcolored ", 31 + index % 7).as_bytes());
+    }
+    term
+}
+
+fn sections(b: &mut Bencher, grid: &Grid) {
+    b.iter(|| {
+        #[allow(deprecated)]
+        for section in grid.sections() {
+            black_box(&section.text);
+        }
+    });
+}
+
+fn style_runs(b: &mut Bencher, grid: &Grid) {
+    b.iter(|| {
+        for run in grid.style_runs() {
+            for cell in &grid[run.line].inner[run.columns] {
+                black_box((cell.c, run.style));
+            }
+        }
+    });
+}
+
+#[bench]
+fn bench_sections_80x24(b: &mut Bencher) {
+    sections(b, colored(80, 24).grid());
+}
+
+#[bench]
+fn bench_style_runs_80x24(b: &mut Bencher) {
+    style_runs(b, colored(80, 24).grid());
+}
+
+#[bench]
+fn bench_sections_300x100(b: &mut Bencher) {
+    sections(b, colored(300, 100).grid());
+}
+
+#[bench]
+fn bench_style_runs_300x100(b: &mut Bencher) {
+    style_runs(b, colored(300, 100).grid());
+}
@@ -0,0 +1,48 @@
+//! Replays an asciicast v2 recording into a headless terminal and prints the
+//! final screen.
+//!
+//! Usage: `replay [--realtime[=SPEED]] FILE`
+
+use std::{env, process::ExitCode};
+
+use vshell::replay::{replay, Pacing};
+use vtty::asciicast::Reader;
+
+fn main() -> ExitCode {
+    let mut pacing = Pacing::Instant;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        if arg == "--realtime" {
+            pacing = Pacing::RealTime(1.0);
+        } else if let Some(speed) = arg.strip_prefix("--realtime=") {
+            match speed.parse() {
+                Ok(speed) if Pacing::is_valid_speed(speed) => pacing = Pacing::RealTime(speed),
+                _ => return usage(&format!("invalid speed: {speed}")),
+            }
+        } else if path.is_none() {
+            path = Some(arg);
+        } else {
+            return usage(&format!("unexpected argument: {arg}"));
+        }
+    }
+    let Some(path) = path else {
+        return usage("missing recording");
+    };
+
+    let result = Reader::open(&path).and_then(|reader| replay(reader, pacing, |_| ()));
+    match result {
+        Ok(term) => {
+            println!("{}", term.grid().screen());
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("unable to replay {path}: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage(message: &str) -> ExitCode {
+    eprintln!("{message}\nusage: replay [--realtime[=SPEED]] FILE");
+    ExitCode::FAILURE
+}
@@ -0,0 +1,146 @@
+//! Character set designation, `ESC ( 0` and friends, with locking and single
+//! shifts.
+
+/// The sets that can be designated into G0-G3.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Charset {
+    #[default]
+    Ascii,
+    /// The UK national set, ASCII with `#` as `£`.
+    Uk,
+    /// DEC Special Graphics, line drawing in place of the lowercase letters.
+    SpecialGraphics,
+}
+
+impl Charset {
+    /// Maps the final byte of a designation like `ESC ( B`.
+    pub fn from_designator(designator: char) -> Option<Self> {
+        match designator {
+            'B' => Some(Self::Ascii),
+            'A' => Some(Self::Uk),
+            '0' => Some(Self::SpecialGraphics),
+            _ => None,
+        }
+    }
+
+    pub fn map(self, c: char) -> char {
+        match self {
+            Self::Ascii => c,
+            Self::Uk => match c {
+                '#' => '£',
+                _ => c,
+            },
+            Self::SpecialGraphics => match c {
+                '_' => ' ',
+                '`' => '◆',
+                'a' => '▒',
+                'b' => '␉',
+                'c' => '␌',
+                'd' => '␍',
+                'e' => '␊',
+                'f' => '°',
+                'g' => '±',
+                'h' => '␤',
+                'i' => '␋',
+                'j' => '┘',
+                'k' => '┐',
+                'l' => '┌',
+                'm' => '└',
+                'n' => '┼',
+                'o' => '⎺',
+                'p' => '⎻',
+                'q' => '─',
+                'r' => '⎼',
+                's' => '⎽',
+                't' => '├',
+                'u' => '┤',
+                'v' => '┴',
+                'w' => '┬',
+                'x' => '│',
+                'y' => '≤',
+                'z' => '≥',
+                '{' => 'π',
+                '|' => '≠',
+                '}' => '£',
+                '~' => '·',
+                _ => c,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CharsetIndex {
+    #[default]
+    G0,
+    G1,
+    G2,
+    G3,
+}
+
+impl CharsetIndex {
+    /// Maps the intermediate of a designation, `(` for G0 up to `+` for G3.
+    pub fn from_intermediate(intermediate: u8) -> Option<Self> {
+        match intermediate {
+            b'(' => Some(Self::G0),
+            b')' => Some(Self::G1),
+            b'*' => Some(Self::G2),
+            b'+' => Some(Self::G3),
+            _ => None,
+        }
+    }
+}
+
+/// The designated sets and the shift state. Part of the cursor, so DECSC and
+/// DECRC save and restore it like xterm does.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Charsets {
+    sets: [Charset; 4],
+    /// The set in GL, chosen by the locking shifts SI, SO, LS2 and LS3.
+    active: CharsetIndex,
+    /// SS2 or SS3, applies to the next character only.
+    single_shift: Option<CharsetIndex>,
+}
+
+impl Charsets {
+    pub fn designate(&mut self, index: CharsetIndex, charset: Charset) {
+        self.sets[index as usize] = charset;
+    }
+
+    pub fn lock(&mut self, index: CharsetIndex) {
+        self.active = index;
+    }
+
+    pub fn single_shift(&mut self, index: CharsetIndex) {
+        self.single_shift = Some(index);
+    }
+
+    /// Maps a printed character, consuming a pending single shift.
+    pub fn translate(&mut self, c: char) -> char {
+        let index = self.single_shift.take().unwrap_or(self.active);
+        self.sets[index as usize].map(c)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shifts() {
+        let mut charsets = Charsets::default();
+        charsets.designate(CharsetIndex::G1, Charset::SpecialGraphics);
+        charsets.designate(CharsetIndex::G2, Charset::Uk);
+        assert_eq!(charsets.translate('q'), 'q');
+
+        charsets.lock(CharsetIndex::G1);
+        assert_eq!(charsets.translate('q'), '─');
+
+        charsets.single_shift(CharsetIndex::G2);
+        assert_eq!(charsets.translate('#'), '£');
+        assert_eq!(charsets.translate('x'), '│');
+
+        charsets.lock(CharsetIndex::G0);
+        assert_eq!(charsets.translate('x'), 'x');
+    }
+}
@@ -1,9 +1,68 @@
 use vui::Vec4;
 
+/// A colour as set by SGR, resolved to RGB through a [`Palette`] only when
+/// drawing.
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-pub struct Color(pub [u8; 3]);
+pub enum Color {
+    /// The configured foreground or background.
+    #[default]
+    Default,
+    /// One of the 16 colours of SGR 30-37 and 90-97.
+    Named(NamedColor),
+    /// An entry of the 256 colour palette, from `38;5;n`.
+    Indexed(u8),
+    Rgb(Rgb),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamedColor {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+}
+
+impl NamedColor {
+    const ALL: [Self; 16] = [
+        Self::Black,
+        Self::Red,
+        Self::Green,
+        Self::Yellow,
+        Self::Blue,
+        Self::Magenta,
+        Self::Cyan,
+        Self::White,
+        Self::BrightBlack,
+        Self::BrightRed,
+        Self::BrightGreen,
+        Self::BrightYellow,
+        Self::BrightBlue,
+        Self::BrightMagenta,
+        Self::BrightCyan,
+        Self::BrightWhite,
+    ];
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub [u8; 3]);
 
-impl Color {
+impl Rgb {
     pub const BLACK: Self = Self::from_rgb(46, 51, 63);
     pub const DARK_GRAY: Self = Self::from_rgb(96, 96, 96);
     pub const GRAY: Self = Self::from_rgb(160, 160, 160);
@@ -34,8 +93,80 @@ impl Color {
     }
 }
 
-impl From<Color> for Vec4 {
-    fn from(c: Color) -> Self {
+impl From<Rgb> for Vec4 {
+    fn from(c: Rgb) -> Self {
         Vec4::new((c.0[0] as f32) / 255.0, (c.0[1] as f32) / 255.0, (c.0[2] as f32) / 255.0, 1.0)
     }
 }
+
+/// Maps [`Color`]s to what is actually drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    pub foreground: Rgb,
+    pub background: Rgb,
+    /// The 16 named colours, then the 6x6x6 cube and the grayscale ramp.
+    pub indexed: [Rgb; 256],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        let mut indexed = [Rgb::default(); 256];
+        indexed[.. 16].copy_from_slice(&[
+            Rgb::BLACK,
+            Rgb::RED,
+            Rgb::GREEN,
+            Rgb::YELLOW,
+            Rgb::BLUE,
+            Rgb::MAGENTA,
+            Rgb::CYAN,
+            Rgb::LIGHT_GRAY,
+            Rgb::DARK_GRAY,
+            Rgb::LIGHT_RED,
+            Rgb::LIGHT_GREEN,
+            Rgb::KHAKI,
+            Rgb::LIGHT_BLUE,
+            Rgb::from_rgb(0xd8, 0xb4, 0xd0),
+            Rgb::from_rgb(0xa8, 0xe0, 0xf0),
+            Rgb::WHITE,
+        ]);
+
+        // The same levels as xterm's 256 colour palette.
+        let level = |i: usize| if i == 0 { 0 } else { (55 + 40 * i) as u8 };
+        for i in 0 .. 216 {
+            indexed[16 + i] = Rgb::from_rgb(level(i / 36), level(i / 6 % 6), level(i % 6));
+        }
+        for i in 0 .. 24 {
+            let gray = (8 + 10 * i) as u8;
+            indexed[232 + i] = Rgb::from_rgb(gray, gray, gray);
+        }
+
+        Self { foreground: Rgb::WHITE, background: Rgb::BLACK, indexed }
+    }
+}
+
+impl Palette {
+    /// `default` is the foreground or background, whichever `color` is for.
+    pub fn resolve(&self, color: Color, default: Rgb) -> Rgb {
+        match color {
+            Color::Default => default,
+            Color::Named(named) => self.indexed[named as usize],
+            Color::Indexed(index) => self.indexed[index as usize],
+            Color::Rgb(rgb) => rgb,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_palette() {
+        let palette = Palette::default();
+
+        assert_eq!(palette.resolve(Color::Default, palette.foreground), Rgb::WHITE);
+        assert_eq!(palette.resolve(Color::Named(NamedColor::Red), Rgb::BLACK), Rgb::RED);
+        assert_eq!(palette.resolve(Color::Indexed(196), Rgb::BLACK), Rgb::from_rgb(255, 0, 0));
+        assert_eq!(palette.resolve(Color::Indexed(244), Rgb::BLACK), Rgb::from_rgb(128, 128, 128));
+    }
+}
@@ -1,29 +1,110 @@
+use std::iter;
+
+use bitflags::bitflags;
+
 use crate::color::Color;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+bitflags! {
+    /// The on/off attributes of SGR.
+    #[derive(Default)]
+    pub struct Flags: u8 {
+        const BOLD = 1 << 0;
+        const DIM = 1 << 1;
+        const ITALIC = 1 << 2;
+        const BLINK = 1 << 3;
+        const INVERSE = 1 << 4;
+        const HIDDEN = 1 << 5;
+        const STRIKETHROUGH = 1 << 6;
+        const OVERLINE = 1 << 7;
+    }
+}
+
+bitflags! {
+    /// How a cell takes part in a character two columns wide.
+    #[derive(Default)]
+    pub struct CellFlags: u8 {
+        /// The first column of a wide character.
+        const WIDE_CHAR = 1 << 0;
+        /// The second column of a wide character, without a character of its
+        /// own.
+        const WIDE_CHAR_SPACER = 1 << 1;
+        /// Left blank in the last column when a wide character didn't fit and
+        /// went to the next line.
+        const LEADING_WIDE_CHAR_SPACER = 1 << 2;
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Underline {
+    #[default]
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub struct Style {
     pub fg: Color,
     pub bg: Color,
-    pub bold: bool,
-    pub underline: bool,
-    pub italics: bool,
+    /// `Color::Default` draws the underline in the foreground colour.
+    pub underline_color: Color,
+    pub flags: Flags,
+    pub underline: Underline,
 }
 
-impl Default for Style {
-    fn default() -> Self {
-        Self { fg: Color::WHITE, bg: Color::BLACK, bold: false, underline: false, italics: false }
-    }
+/// What only few cells need, kept out of line so the others stay small.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CellExtra {
+    /// Combining marks, joiners and the rest of a grapheme cluster after the
+    /// cell's `c`.
+    pub zerowidth: Vec<char>,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct Cell {
     pub c: Option<char>,
     pub style: Style,
+    pub flags: CellFlags,
+    /// `None` until something is attached to the cell.
+    pub extra: Option<Box<CellExtra>>,
 }
 
 impl Cell {
     pub fn new() -> Self {
-        Self { c: None, style: Default::default() }
+        Self { c: None, style: Default::default(), flags: CellFlags::empty(), extra: None }
+    }
+
+    /// An erased cell. Only the background of the cursor's template carries
+    /// over, so cleared areas keep the application's background colour.
+    pub fn blank(template: Style) -> Self {
+        Self {
+            c: None,
+            style: Style { bg: template.bg, ..Default::default() },
+            flags: CellFlags::empty(),
+            extra: None,
+        }
+    }
+
+    pub fn zerowidth(&self) -> &[char] {
+        self.extra.as_ref().map_or(&[], |extra| &extra.zerowidth)
+    }
+
+    pub fn push_zerowidth(&mut self, c: char) {
+        self.extra.get_or_insert_with(Default::default).zerowidth.push(c);
+    }
+
+    /// The whole grapheme cluster in the cell, a space if it's empty.
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        iter::once(self.c.unwrap_or(' ')).chain(self.zerowidth().iter().copied())
+    }
+
+    /// Whether nothing was printed here. The spacer of a wide character isn't
+    /// empty, it belongs to the character left of it.
+    pub fn is_empty(&self) -> bool {
+        self.c.is_none() && !self.flags.contains(CellFlags::WIDE_CHAR_SPACER)
     }
 }
 
@@ -0,0 +1,46 @@
+use crate::{charset::Charsets, grid::cell::Style};
+
+/// Where the next character goes, and what it looks like.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Cursor {
+    pub line: usize,
+    pub column: usize,
+    /// The style given to printed and erased cells.
+    pub template: Style,
+    /// Set after printing into the last column. The cursor stays on that
+    /// column and the line only wraps once the next character arrives.
+    pub input_needs_wrap: bool,
+    pub charsets: Charsets,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum CursorShape {
+    #[default]
+    Block,
+    Underline,
+    Beam,
+}
+
+/// How the renderer draws the cursor, as set by DECSCUSR.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct CursorStyle {
+    pub shape: CursorShape,
+    pub blinking: bool,
+}
+
+impl CursorStyle {
+    /// Maps the parameter of `CSI Ps SP q`. 0 and 1 both mean a blinking
+    /// block in xterm.
+    pub fn from_decscusr(param: u16) -> Option<Self> {
+        let (shape, blinking) = match param {
+            0 | 1 => (CursorShape::Block, true),
+            2 => (CursorShape::Block, false),
+            3 => (CursorShape::Underline, true),
+            4 => (CursorShape::Underline, false),
+            5 => (CursorShape::Beam, true),
+            6 => (CursorShape::Beam, false),
+            _ => return None,
+        };
+        Some(Self { shape, blinking })
+    }
+}
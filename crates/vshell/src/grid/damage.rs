@@ -0,0 +1,193 @@
+//! What changed on the screen since the last frame, so a renderer only
+//! rebuilds the lines that need it.
+
+use std::ops::Range;
+
+use crate::grid::Grid;
+
+/// The damage since [`Grid::reset_damage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Damage {
+    /// Redraw everything, e.g. after a resize or a switch of screens.
+    Full,
+    Partial {
+        /// How far the whole screen scrolled up. Lines drawn before move up
+        /// by as much, the ones that scrolled in are among `lines`.
+        scrolled: usize,
+        lines: Vec<LineDamage>,
+    },
+}
+
+/// The changed columns of a line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineDamage {
+    pub line: usize,
+    pub columns: Range<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct DamageTracker {
+    full: bool,
+    scrolled: usize,
+    lines: Vec<Option<Range<usize>>>,
+    columns: usize,
+    /// Where the cursor was drawn, its old line needs a redraw once it moved.
+    cursor: (usize, usize),
+}
+
+impl DamageTracker {
+    pub fn new(columns: usize, lines: usize) -> Self {
+        Self { full: true, scrolled: 0, lines: vec![None; lines], columns, cursor: (0, 0) }
+    }
+
+    pub fn damage_cells(&mut self, line: usize, columns: Range<usize>) {
+        let columns = columns.start .. columns.end.min(self.columns);
+        if columns.is_empty() {
+            return;
+        }
+        let damage = &mut self.lines[line];
+        *damage = Some(match damage.take() {
+            Some(damage) => damage.start.min(columns.start) .. damage.end.max(columns.end),
+            None => columns,
+        });
+    }
+
+    pub fn damage_lines(&mut self, lines: Range<usize>) {
+        for line in lines {
+            self.damage_cells(line, 0 .. self.columns);
+        }
+    }
+
+    /// The whole screen scrolled up, what was damaged moves with it.
+    pub fn scroll_up(&mut self, count: usize) {
+        let lines = self.lines.len();
+        self.scrolled += count;
+        if self.scrolled >= lines {
+            self.full = true;
+            return;
+        }
+        self.lines.rotate_left(count);
+        self.lines[lines - count ..].fill(None);
+        self.damage_lines(lines - count .. lines);
+    }
+
+    pub fn resize(&mut self, columns: usize, lines: usize) {
+        *self = Self { cursor: self.cursor, ..Self::new(columns, lines) };
+    }
+
+    pub fn mark_full(&mut self) {
+        self.full = true;
+    }
+}
+
+impl Grid {
+    /// What changed since the last [`Grid::reset_damage`], including the
+    /// lines the cursor moved between. Cells changed by indexing into the
+    /// grid directly aren't tracked.
+    pub fn damage(&self) -> Damage {
+        let tracker = &self.damage;
+        if tracker.full {
+            return Damage::Full;
+        }
+
+        let mut lines = tracker.lines.clone();
+        let cursor = (self.cursor.line, self.cursor.column);
+        if cursor != tracker.cursor {
+            // The old position moved up with the text, or scrolled off.
+            let old = tracker.cursor.0.checked_sub(tracker.scrolled);
+            for line in old.into_iter().chain([cursor.0]) {
+                if let Some(damage) = lines.get_mut(line) {
+                    *damage = Some(0 .. tracker.columns);
+                }
+            }
+        }
+
+        let lines = lines
+            .into_iter()
+            .enumerate()
+            .filter_map(|(line, columns)| Some(LineDamage { line, columns: columns? }))
+            .collect();
+        Damage::Partial { scrolled: tracker.scrolled, lines }
+    }
+
+    /// Called once a frame was drawn.
+    pub fn reset_damage(&mut self) {
+        let tracker = &mut self.damage;
+        tracker.full = false;
+        tracker.scrolled = 0;
+        tracker.lines.fill(None);
+        tracker.cursor = (self.cursor.line, self.cursor.column);
+    }
+
+    /// The cursor's line needs a redraw, e.g. after the cursor was hidden or
+    /// changed its shape.
+    pub fn damage_cursor(&mut self) {
+        let line = self.cursor.line;
+        self.damage.damage_lines(line .. line + 1);
+    }
+
+    /// Everything needs a redraw, e.g. when this grid becomes the one shown.
+    pub fn mark_fully_damaged(&mut self) {
+        self.damage.mark_full();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn damaged_lines(g: &Grid) -> Vec<(usize, Range<usize>)> {
+        match g.damage() {
+            Damage::Full => panic!("fully damaged"),
+            Damage::Partial { lines, .. } => {
+                lines.into_iter().map(|damage| (damage.line, damage.columns)).collect()
+            }
+        }
+    }
+
+    #[test]
+    fn test_damage() {
+        let mut g = Grid::new(6, 3);
+        assert_eq!(g.damage(), Damage::Full);
+        g.reset_damage();
+        assert_eq!(damaged_lines(&g), []);
+
+        g.goto(1, 2);
+        g.write_char('a');
+        g.write_char('b');
+        assert_eq!(damaged_lines(&g), [(0, 0 .. 6), (1, 0 .. 6)]);
+
+        // Moving back where it was drawn leaves nothing to redraw for it.
+        g.reset_damage();
+        g.goto(2, 0);
+        assert_eq!(damaged_lines(&g), [(1, 0 .. 6), (2, 0 .. 6)]);
+        g.goto(1, 4);
+        assert_eq!(damaged_lines(&g), []);
+
+        g.damage_cursor();
+        assert_eq!(damaged_lines(&g), [(1, 0 .. 6)]);
+
+        g.reset_damage();
+        g.resize(4, 3);
+        assert_eq!(g.damage(), Damage::Full);
+    }
+
+    #[test]
+    fn test_scroll_damage() {
+        let mut g = Grid::new(4, 3);
+        g.reset_damage();
+        g.goto(0, 0);
+        g.write_char('a');
+        g.goto(2, 0);
+        g.linefeed();
+
+        assert_eq!(
+            g.damage(),
+            Damage::Partial { scrolled: 1, lines: vec![LineDamage { line: 2, columns: 0 .. 4 }] }
+        );
+
+        g.linefeed();
+        g.linefeed();
+        assert_eq!(g.damage(), Damage::Full);
+    }
+}
@@ -0,0 +1,613 @@
+//! Cursor movement and editing, as used by the CSI sequences of the same
+//! names.
+
+use std::{mem, ops::Range};
+
+use unicode_segmentation::GraphemeCursor;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+use crate::grid::{
+    cell::{Cell, CellFlags},
+    Grid,
+};
+
+/// What ED erases.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClearMode {
+    /// From the cursor to the end of the screen.
+    Below,
+    /// From the start of the screen through the cursor.
+    Above,
+    All,
+    /// The scrollback only, the screen stays as it is.
+    Saved,
+}
+
+/// The cell printed to last, and where that left the cursor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(super) struct Printed {
+    column: usize,
+    cursor: (usize, usize, bool),
+}
+
+/// What EL erases.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LineClearMode {
+    Right,
+    Left,
+    All,
+}
+
+impl Grid {
+    /// What erased cells are filled with.
+    pub fn blank(&self) -> Cell {
+        Cell::blank(self.cursor.template)
+    }
+
+    /// Prints `c` at the cursor and moves past it, first wrapping if the last
+    /// character filled the line. Wide characters take two cells, one that
+    /// doesn't fit in the last column goes to the next line whole.
+    ///
+    /// A character that continues the grapheme cluster printed last joins its
+    /// cell instead, which becomes wide if the whole cluster is.
+    pub fn write_char(&mut self, c: char) {
+        if self.join_printed(c) {
+            return;
+        }
+        let width = match c.width() {
+            // Nothing to combine with, like a mark after the cursor moved.
+            Some(0) => return,
+            Some(2) => 2,
+            _ => 1,
+        };
+
+        let style = self.cursor.template;
+        let cell = Cell { c: Some(c), style, flags: CellFlags::empty(), extra: None };
+        if self.put_cluster(cell, width) {
+            self.cluster.clear();
+            self.cluster.push(c);
+        }
+    }
+
+    /// Puts a cluster's first cell at the cursor, wrapping first if needed,
+    /// and moves past it. False if it doesn't fit between the margins.
+    fn put_cluster(&mut self, cell: Cell, width: usize) -> bool {
+        if self.cursor.input_needs_wrap {
+            self.wrap_line();
+        }
+
+        let mut end = self.line_end();
+        if self.cursor.column + width > end {
+            let spacer = Cell { flags: CellFlags::LEADING_WIDE_CHAR_SPACER, ..self.blank() };
+            self.put_cells(&[spacer]);
+            self.wrap_line();
+            // Margins narrower than the character.
+            end = self.line_end();
+            if self.cursor.column + width > end {
+                return false;
+            }
+        }
+
+        if width == 2 {
+            let spacer = Cell {
+                c: None,
+                style: cell.style,
+                flags: CellFlags::WIDE_CHAR_SPACER,
+                extra: None,
+            };
+            self.put_cells(&[Cell { flags: CellFlags::WIDE_CHAR, ..cell }, spacer]);
+        } else {
+            self.put_cells(&[cell]);
+        }
+
+        let column = self.cursor.column;
+        if self.cursor.column + width < end {
+            self.cursor.column += width;
+        } else {
+            self.cursor.column = end - 1;
+            self.cursor.input_needs_wrap = true;
+        }
+        self.printed = Some(Printed { column, cursor: self.cursor_position() });
+        true
+    }
+
+    fn cursor_position(&self) -> (usize, usize, bool) {
+        (self.cursor.line, self.cursor.column, self.cursor.input_needs_wrap)
+    }
+
+    /// Continues the line on the next row.
+    fn wrap_line(&mut self) {
+        self.storage[self.cursor.line].wrapped = true;
+        self.carriage_return();
+        self.linefeed();
+    }
+
+    /// Attaches `c` to the cluster printed last if there's no grapheme
+    /// boundary between them, like for a combining accent, a spacing mark, a
+    /// variation selector or the rest of an emoji sequence. Only right after
+    /// printing, moving the cursor starts a new cluster.
+    fn join_printed(&mut self, c: char) -> bool {
+        let cursor = self.cursor_position();
+        let Some(printed) = self.printed.filter(|printed| printed.cursor == cursor) else {
+            return false;
+        };
+        // Two ASCII characters are never one cluster, skip the segmentation.
+        if c.is_ascii() && self.cluster.is_ascii() {
+            return false;
+        }
+
+        let start = self.cluster.len();
+        self.cluster.push(c);
+        let mut boundary = GraphemeCursor::new(start, self.cluster.len(), true);
+        let cell = &mut self.storage[self.cursor.line][printed.column];
+        if cell.c.is_none() || boundary.is_boundary(&self.cluster, 0).unwrap_or(true) {
+            self.cluster.truncate(start);
+            return false;
+        }
+        cell.push_zerowidth(c);
+        let widen = self.cluster.width() > 1 && !cell.flags.contains(CellFlags::WIDE_CHAR);
+        self.damage_cursor_line(printed.column .. printed.column + 1);
+
+        if widen {
+            self.widen_printed(printed.column);
+        }
+        true
+    }
+
+    /// Makes a printed cell wide once its cluster is, like a flag after its
+    /// second regional indicator. It moves to the next line if it's in the
+    /// last column, as if it had been printed wide in the first place.
+    fn widen_printed(&mut self, column: usize) {
+        let blank = self.blank();
+        let cell = mem::replace(&mut self.storage[self.cursor.line][column], blank);
+        self.cursor.column = column;
+        self.cursor.input_needs_wrap = false;
+        self.put_cluster(cell, 2);
+    }
+
+    /// Forgets the character printed last, after the cursor or the text
+    /// under it moved.
+    pub(super) fn forget_printed(&mut self) {
+        self.printed = None;
+    }
+
+    /// Overwrites cells from the cursor on, along with the other half of any
+    /// wide character they cut in two.
+    fn put_cells(&mut self, cells: &[Cell]) {
+        let blank = self.blank();
+        let column = self.cursor.column;
+        let row = &mut self.storage[self.cursor.line];
+        row.split_wide_char(column, &blank);
+        row.split_wide_char(column + cells.len(), &blank);
+        row.inner[column .. column + cells.len()].clone_from_slice(cells);
+        self.damage_cursor_line(column .. column + cells.len());
+    }
+
+    /// Damages `columns` of the cursor's line, and the cells next to them a
+    /// split wide character may have blanked.
+    fn damage_cursor_line(&mut self, columns: Range<usize>) {
+        let columns = columns.start.saturating_sub(1) .. columns.end + 1;
+        self.damage.damage_cells(self.cursor.line, columns);
+    }
+
+    /// Moves the cursor, keeping it on the grid.
+    pub fn goto(&mut self, line: usize, column: usize) {
+        self.cursor.line = line.min(self.lines() - 1);
+        self.cursor.column = column.min(self.columns - 1);
+        self.cursor.input_needs_wrap = false;
+        self.forget_printed();
+    }
+
+    /// BS, stopping at the first column.
+    pub fn backspace(&mut self) {
+        self.goto_column(self.cursor.column.saturating_sub(1));
+    }
+
+    pub fn goto_line(&mut self, line: usize) {
+        self.goto(line, self.cursor.column);
+    }
+
+    pub fn goto_column(&mut self, column: usize) {
+        self.goto(self.cursor.line, column);
+    }
+
+    /// CUU, stopping at the top of the scroll region if the cursor is in it.
+    pub fn move_up(&mut self, count: usize) {
+        let line = self.cursor.line;
+        let top = if line >= self.scroll_region.start { self.scroll_region.start } else { 0 };
+        self.goto_line(line.saturating_sub(count).max(top));
+    }
+
+    /// CUD, stopping at the bottom of the scroll region if the cursor is in
+    /// it.
+    pub fn move_down(&mut self, count: usize) {
+        let line = self.cursor.line;
+        let bottom =
+            if line < self.scroll_region.end { self.scroll_region.end } else { self.lines() };
+        self.goto_line(line.saturating_add(count).min(bottom - 1));
+    }
+
+    /// CUF, stopping at the right margin if the cursor is left of it.
+    pub fn move_forward(&mut self, count: usize) {
+        let end = self.line_end();
+        self.goto_column(self.cursor.column.saturating_add(count).min(end - 1));
+    }
+
+    /// CUB, stopping at the left margin if the cursor is right of it.
+    pub fn move_backward(&mut self, count: usize) {
+        let column = self.cursor.column;
+        let start = if column >= self.margins.start { self.margins.start } else { 0 };
+        self.goto_column(column.saturating_sub(count).max(start));
+    }
+
+    /// HT and CHT, stopping at the right margin.
+    pub fn tab_forward(&mut self, count: usize) {
+        let end = self.line_end() - 1;
+        let mut column = self.cursor.column;
+        for _ in 0 .. count {
+            match self.tabs.next(column) {
+                Some(stop) if stop < end => column = stop,
+                _ => {
+                    column = end;
+                    break;
+                }
+            }
+        }
+        self.goto_column(column);
+    }
+
+    /// CBT, stopping at the left margin.
+    pub fn tab_backward(&mut self, count: usize) {
+        let start = if self.cursor.column >= self.margins.start { self.margins.start } else { 0 };
+        let mut column = self.cursor.column;
+        for _ in 0 .. count {
+            match self.tabs.prev(column) {
+                Some(stop) if stop > start => column = stop,
+                _ => {
+                    column = start;
+                    break;
+                }
+            }
+        }
+        self.goto_column(column);
+    }
+
+    /// HTS, a tab stop at the cursor.
+    pub fn set_tab_stop(&mut self) {
+        self.tabs.set(self.cursor.column);
+    }
+
+    /// TBC 0, clears the tab stop at the cursor.
+    pub fn clear_tab_stop(&mut self) {
+        self.tabs.clear(self.cursor.column);
+    }
+
+    /// TBC 3.
+    pub fn clear_all_tab_stops(&mut self) {
+        self.tabs.clear_all();
+    }
+
+    pub fn clear_screen(&mut self, mode: ClearMode) {
+        let blank = self.blank();
+        let line = self.cursor.line;
+        match mode {
+            ClearMode::Below => {
+                self.clear_line(LineClearMode::Right);
+                for line in line + 1 .. self.lines() {
+                    self.storage[line].reset(&blank);
+                }
+                self.damage.damage_lines(line + 1 .. self.lines());
+            }
+            ClearMode::Above => {
+                self.clear_line(LineClearMode::Left);
+                for line in 0 .. line {
+                    self.storage[line].reset(&blank);
+                }
+                self.damage.damage_lines(0 .. line);
+            }
+            ClearMode::All => {
+                for line in 0 .. self.lines() {
+                    self.storage[line].reset(&blank);
+                }
+                self.damage.damage_lines(0 .. self.lines());
+            }
+            ClearMode::Saved => self.storage.clear_history(),
+        }
+    }
+
+    pub fn clear_line(&mut self, mode: LineClearMode) {
+        let blank = self.blank();
+        let column = self.cursor.column;
+        let row = &mut self.storage[self.cursor.line];
+        let columns = match mode {
+            LineClearMode::Right => {
+                row.split_wide_char(column, &blank);
+                row.inner[column ..].fill(blank);
+                // Nothing is left to go on to the next row.
+                row.wrapped = false;
+                column .. self.columns
+            }
+            LineClearMode::Left => {
+                row.split_wide_char(column + 1, &blank);
+                row.inner[..= column].fill(blank);
+                0 .. column + 1
+            }
+            LineClearMode::All => {
+                row.reset(&blank);
+                0 .. self.columns
+            }
+        };
+        self.damage_cursor_line(columns);
+    }
+
+    /// ECH, blanks `count` cells from the cursor on without moving the rest.
+    pub fn erase_chars(&mut self, count: usize) {
+        let blank = self.blank();
+        let column = self.cursor.column;
+        let end = column.saturating_add(count).min(self.columns);
+        let row = &mut self.storage[self.cursor.line];
+        row.split_wide_char(column, &blank);
+        row.split_wide_char(end, &blank);
+        row.inner[column .. end].fill(blank);
+        if end == self.columns {
+            row.wrapped = false;
+        }
+        self.damage_cursor_line(column .. end);
+    }
+
+    /// ICH, shifts the rest of the line right. Cells pushed past the right
+    /// margin are lost.
+    pub fn insert_blank(&mut self, count: usize) {
+        self.forget_printed();
+        let blank = self.blank();
+        let column = self.cursor.column;
+        let end = self.line_end();
+        let count = count.min(end - column);
+        let row = &mut self.storage[self.cursor.line];
+        for boundary in [column, end - count, end] {
+            row.split_wide_char(boundary, &blank);
+        }
+        let row = &mut row.inner[.. end];
+        row[column ..].rotate_right(count);
+        row[column .. column + count].fill(blank);
+        self.damage_cursor_line(column .. end);
+    }
+
+    /// DCH, shifts the rest of the line left and blanks its end, up to the
+    /// right margin.
+    pub fn delete_chars(&mut self, count: usize) {
+        self.forget_printed();
+        let blank = self.blank();
+        let column = self.cursor.column;
+        let end = self.line_end();
+        let count = count.min(end - column);
+        let row = &mut self.storage[self.cursor.line];
+        for boundary in [column, column + count, end] {
+            row.split_wide_char(boundary, &blank);
+        }
+        let row = &mut row.inner[.. end];
+        row[column ..].rotate_left(count);
+        let len = row.len();
+        row[len - count ..].fill(blank);
+        self.damage_cursor_line(column .. end);
+    }
+
+    /// IL, pushes the cursor's line and those below it down. Lines pushed
+    /// past the bottom of the scroll region are lost.
+    pub fn insert_lines(&mut self, count: usize) {
+        if self.cursor_in_region() {
+            self.shift_down(self.cursor.line .. self.scroll_region.end, count);
+            self.carriage_return();
+        }
+    }
+
+    /// DL, pulls the lines below the cursor up and blanks the bottom of the
+    /// scroll region.
+    pub fn delete_lines(&mut self, count: usize) {
+        if self.cursor_in_region() {
+            self.shift_up(self.cursor.line .. self.scroll_region.end, count);
+            self.carriage_return();
+        }
+    }
+
+    fn cursor_in_region(&self) -> bool {
+        self.scroll_region.contains(&self.cursor.line) && self.margins.contains(&self.cursor.column)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        color::{Color, NamedColor},
+        grid::cell::Flags,
+    };
+
+    /// A grid with one letter per line, starting at `a`.
+    fn letters(columns: usize, lines: usize) -> Grid {
+        let mut g = Grid::new(columns, lines);
+        for line in 0 .. lines {
+            for column in 0 .. columns {
+                g[line][column].c = Some((b'a' + line as u8) as char);
+            }
+        }
+        g
+    }
+
+    #[test]
+    fn test_goto_clamps() {
+        let mut g = Grid::new(4, 3);
+        g.cursor.input_needs_wrap = true;
+        g.goto(10, 10);
+
+        assert_eq!((g.cursor.line, g.cursor.column), (2, 3));
+        assert!(!g.cursor.input_needs_wrap);
+    }
+
+    #[test]
+    fn test_tab_at_last_column() {
+        let mut g = Grid::new(4, 2);
+        for c in "abcd".chars() {
+            g.write_char(c);
+        }
+        assert!(g.cursor.input_needs_wrap);
+
+        g.tab_forward(1);
+        g.write_char('e');
+        assert_eq!(g.screen(), "abce\n");
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut g = letters(3, 3);
+        g.goto(1, 1);
+        g.clear_screen(ClearMode::Below);
+        assert_eq!(g.screen(), "aaa\nb\n");
+
+        let mut g = letters(3, 3);
+        g.goto(1, 1);
+        g.clear_screen(ClearMode::Above);
+        assert_eq!(g.screen(), "\n  b\nccc");
+
+        let mut g = letters(3, 3);
+        g.goto(1, 1);
+        g.clear_line(LineClearMode::All);
+        assert_eq!(g.screen(), "aaa\n\nccc");
+    }
+
+    #[test]
+    fn test_wide_chars() {
+        let mut g = Grid::new(4, 2);
+        for c in "a中文".chars() {
+            g.write_char(c);
+        }
+        assert_eq!(g.screen(), "a中\n文");
+        assert!(g[1][0].flags.contains(CellFlags::WIDE_CHAR));
+        assert!(g[0][3].flags.contains(CellFlags::LEADING_WIDE_CHAR_SPACER));
+
+        g.goto(0, 2);
+        g.write_char('b');
+        assert_eq!(g.screen(), "a b\n文");
+
+        g.goto(1, 0);
+        g.write_char('c');
+        assert_eq!(g.screen(), "a b\nc");
+        assert!(g[1].inner.iter().all(|cell| cell.flags.is_empty()));
+    }
+
+    #[test]
+    fn test_grapheme_clusters() {
+        let mut g = Grid::new(10, 2);
+        for c in "👨\u{200d}👩🇩🇪🇫🇷".chars() {
+            g.write_char(c);
+        }
+        let cells: Vec<String> = g[0]
+            .inner
+            .iter()
+            .filter(|cell| cell.c.is_some())
+            .map(|cell| cell.chars().collect())
+            .collect();
+        assert_eq!(cells, ["👨\u{200d}👩", "🇩🇪", "🇫🇷"]);
+
+        // Flags take two columns, like the family before them.
+        assert!(g[0][2].flags.contains(CellFlags::WIDE_CHAR));
+        assert_eq!(g[0][4].chars().collect::<String>(), "🇫🇷");
+        assert_eq!(g.cursor.column, 6);
+
+        // Moving away and back starts a new cluster, the mark has nothing to
+        // combine with then.
+        g.goto(1, 0);
+        g.write_char('e');
+        g.move_backward(1);
+        g.move_forward(1);
+        g.write_char('\u{301}');
+        assert!(g[1][0].zerowidth().is_empty());
+        assert_eq!(g[1][1].c, None);
+        assert_eq!(g.cursor.column, 1);
+    }
+
+    #[test]
+    fn test_spacing_marks() {
+        let mut g = Grid::new(4, 1);
+        for c in "कि".chars() {
+            g.write_char(c);
+        }
+        assert_eq!(g[0][0].chars().collect::<String>(), "कि");
+        assert!(g[0][0].flags.contains(CellFlags::WIDE_CHAR));
+        assert_eq!(g.cursor.column, 2);
+    }
+
+    #[test]
+    fn test_cluster_widened_in_last_column() {
+        let mut g = Grid::new(3, 2);
+        for c in "ab🇩🇪".chars() {
+            g.write_char(c);
+        }
+        assert_eq!(g.screen(), "ab\n🇩🇪");
+        assert!(g[0][2].flags.contains(CellFlags::LEADING_WIDE_CHAR_SPACER));
+        assert!(g[1][0].flags.contains(CellFlags::WIDE_CHAR));
+        assert_eq!((g.cursor.line, g.cursor.column), (1, 2));
+    }
+
+    #[test]
+    fn test_edits_split_wide_chars() {
+        let mut g = Grid::new(6, 1);
+        for c in "中文字".chars() {
+            g.write_char(c);
+        }
+        g.goto(0, 1);
+        g.delete_chars(2);
+        assert_eq!(g.screen(), "  字");
+
+        g.goto(0, 3);
+        g.insert_blank(1);
+        assert_eq!(g.screen(), "");
+
+        g.goto(0, 0);
+        g.write_char('中');
+        g.goto(0, 1);
+        g.erase_chars(1);
+        assert_eq!(g.screen(), "");
+    }
+
+    #[test]
+    fn test_erased_cells_take_the_background() {
+        let mut g = letters(3, 1);
+        g.cursor.template.bg = Color::Named(NamedColor::Red);
+        g.cursor.template.flags = Flags::BOLD;
+        g.erase_chars(2);
+
+        assert_eq!(g.screen(), "  a");
+        assert_eq!(g[0][0].style.bg, Color::Named(NamedColor::Red));
+        assert!(g[0][0].style.flags.is_empty());
+    }
+
+    #[test]
+    fn test_insert_and_delete_chars() {
+        let mut g = Grid::new(5, 1);
+        "abcde".chars().enumerate().for_each(|(i, c)| g[0][i].c = Some(c));
+        g.goto(0, 1);
+        g.insert_blank(2);
+        assert_eq!(g.screen(), "a  bc");
+
+        g.delete_chars(3);
+        assert_eq!(g.screen(), "ac");
+
+        g.insert_blank(100);
+        assert_eq!(g.screen(), "a");
+    }
+
+    #[test]
+    fn test_insert_and_delete_lines() {
+        let mut g = letters(1, 4);
+        g.goto(1, 0);
+        g.insert_lines(2);
+        assert_eq!(g.screen(), "a\n\n\nb");
+
+        g.delete_lines(1);
+        assert_eq!(g.screen(), "a\n\nb\n");
+
+        g.scroll_down_lines(1);
+        assert_eq!(g.screen(), "\na\n\nb");
+    }
+}
@@ -1,74 +1,146 @@
 use std::{
     fmt::Display,
-    ops::{Index, IndexMut},
+    ops::{Index, IndexMut, Range},
 };
 
 use self::{
-    cell::{Cell, Style},
+    cell::{Cell, CellFlags, Style},
+    cursor::Cursor,
+    damage::DamageTracker,
+    edit::Printed,
     row::Row,
+    storage::Storage,
+    tabs::TabStops,
+};
+pub use self::{
+    damage::{Damage, LineDamage},
+    edit::{ClearMode, LineClearMode},
+    runs::{StyleRun, StyleRuns},
 };
 
 pub mod cell;
+pub mod cursor;
+mod damage;
+mod edit;
+mod resize;
 pub mod row;
+mod runs;
+mod scroll;
+mod storage;
+pub mod tabs;
+
+/// How many rows of scrollback a grid keeps unless told otherwise.
+pub const DEFAULT_SCROLLBACK_LIMIT: usize = 10_000;
 
 #[derive(Debug)]
 pub struct Grid {
-    rows: Vec<Row>,
-    scrollback: Vec<Row>,
+    /// The scrollback and the screen.
+    storage: Storage,
     index: usize,
     columns: usize,
+    pub cursor: Cursor,
+    /// Stored by DECSC and `CSI s`, `None` until then.
+    saved_cursor: Option<Cursor>,
+    /// Where the last character went, for the next to join its cluster.
+    printed: Option<Printed>,
+    /// The text of that cluster, kept to find where it ends.
+    cluster: String,
+    scroll_region: Range<usize>,
+    margins: Range<usize>,
+    /// DECLRMM, whether the left and right margins can be set.
+    margin_mode: bool,
+    tabs: TabStops,
+    damage: DamageTracker,
 }
 
 impl Grid {
     pub fn new(columns: usize, lines: usize) -> Self {
-        let mut rows = Vec::with_capacity(lines);
-        rows.resize(lines, Row::new(columns));
+        Self {
+            storage: Storage::new(columns, lines, DEFAULT_SCROLLBACK_LIMIT),
+            index: 0,
+            columns,
+            cursor: Cursor::default(),
+            saved_cursor: None,
+            printed: None,
+            cluster: String::new(),
+            scroll_region: 0 .. lines,
+            margins: 0 .. columns,
+            margin_mode: false,
+            tabs: TabStops::new(columns),
+            damage: DamageTracker::new(columns, lines),
+        }
+    }
 
-        Self { rows, index: 0, scrollback: vec![], columns }
+    /// Limits how many rows scroll into the scrollback, 0 disables it.
+    pub fn with_scrollback_limit(mut self, limit: usize) -> Self {
+        self.set_scrollback_limit(limit);
+        self
     }
 
-    /// Scrolls the grid up by one
-    pub fn scroll_up(&mut self) {
-        let len = self.rows.len();
-        for i in 1 .. len {
-            self.rows.swap(i - 1, i);
-        }
-        self.scrollback.push(self.rows[len - 1].clone());
-        self.rows[len - 1].reset();
+    /// Drops the oldest rows beyond a lower limit right away.
+    pub fn set_scrollback_limit(&mut self, limit: usize) {
+        self.storage.set_max_history(limit);
     }
 
-    /// Scrolls the grid down by one, taking the last row from the scrollback
-    pub fn scroll_down(&mut self) {
-        let len = self.rows.len();
-        for i in (1 .. len).rev() {
-            self.rows.swap(i - 1, i);
-        }
-        if !self.scrollback.is_empty() {
-            self.rows[0] = self.scrollback.pop().unwrap();
+    /// How many rows scrolled off into the scrollback.
+    pub fn history_len(&self) -> usize {
+        self.storage.history_len()
+    }
+
+    /// A row of the scrollback, 0 being the oldest.
+    pub fn history(&self, index: usize) -> &Row {
+        self.storage.history(index)
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn lines(&self) -> usize {
+        self.storage.lines()
+    }
+
+    pub fn save_cursor(&mut self) {
+        self.saved_cursor = Some(self.cursor);
+    }
+
+    /// Restores the saved cursor, or moves it home with the default style if
+    /// nothing was saved, like xterm.
+    pub fn restore_cursor(&mut self) {
+        self.cursor = self.saved_cursor.unwrap_or_default();
+        self.clamp_cursor();
+        self.forget_printed();
+    }
+
+    /// Keeps the cursor on the grid after its size changed.
+    fn clamp_cursor(&mut self) {
+        for cursor in [Some(&mut self.cursor), self.saved_cursor.as_mut()].into_iter().flatten() {
+            cursor.line = cursor.line.min(self.storage.lines() - 1);
+            cursor.column = cursor.column.min(self.columns - 1);
         }
     }
 
     /// Returns the different style sections to render.
-    /// Note: this thing allocates too much, make it so that it returns ranges
-    /// instead and stop allocating things in a tight renderer loop.
+    /// Note: this thing allocates too much, [`Grid::style_runs`] borrows the
+    /// cells instead.
+    #[deprecated(note = "use Grid::style_runs, it borrows the cells")]
     pub fn sections(&self) -> Vec<TextSection> {
         let mut res = vec![];
 
-        let mut current_style = self.rows[0][0].style;
+        let mut current_style = self.storage[0][0].style;
         let mut text = String::new();
 
-        for row in &self.rows {
-            for col in &row.inner {
+        for row in self.storage.screen() {
+            // The renderer draws wide characters across their spacer.
+            for col in
+                row.inner.iter().filter(|cell| !cell.flags.contains(CellFlags::WIDE_CHAR_SPACER))
+            {
                 if col.style != current_style {
                     res.push(TextSection { text: text.clone(), style: current_style });
                     text = "".to_string();
                     current_style = col.style;
                 }
-                if let Some(c) = col.c {
-                    text.push_str(&String::from(c));
-                } else {
-                    text.push(' ');
-                }
+                text.extend(col.chars());
             }
             text.push('\n');
         }
@@ -81,63 +153,30 @@ impl Grid {
         res
     }
 
-    pub fn resize(&mut self, new_columns: usize, new_lines: usize) {
-        let mut new_rows: Vec<Row> = Vec::new();
-        let mut current_row = Row::new(new_columns);
-        let mut current_column_index = 0;
-
-        // Flatten all cells from existing rows into a single vector
-        let all_cells: Vec<Cell> = self.rows.iter().flat_map(|r| r.inner.clone()).collect();
-
-        let mut advance = false;
-        // Wrap cells into new rows based on the new column width
-        for cell in all_cells {
-            if advance && cell.c.is_none() {
-                continue;
-            } else {
-                advance = false;
-            }
-
-            // If we arrived at the end of the row
-            if current_column_index == new_columns {
-                new_rows.push(current_row);
-                current_row = Row::new(new_columns);
-                current_column_index = 0;
-            }
-
-            if cell.c.is_some() {
-                current_row[current_column_index] = cell;
-                current_column_index += 1;
-            } else {
-                new_rows.push(current_row);
-                current_row = Row::new(new_columns);
-                current_column_index = 0;
-                advance = true;
-            }
-
-            // Break out of the loop early if we've filled up the new_lines
-            if new_rows.len() == new_lines {
-                break;
-            }
-        }
-
-        // Add the last row if it's not empty and we haven't exceeded new_lines
-        if !current_row.inner.is_empty() && new_rows.len() < new_lines {
-            new_rows.push(current_row);
-        }
-
-        // If the new size has more lines than the current content, add empty
-        // rows
-        while new_rows.len() < new_lines {
-            new_rows.push(Row::new(new_columns));
-        }
-
-        // Update grid rows and columns
-        self.rows = new_rows;
-        self.columns = new_columns; // Update the column count
+    /// The visible screen as plain text, one line per row with trailing blanks
+    /// trimmed.
+    pub fn screen(&self) -> String {
+        let lines: Vec<String> = self
+            .storage
+            .screen()
+            .map(|row| {
+                let line: String = row
+                    .inner
+                    .iter()
+                    .filter(|cell| !cell.flags.contains(CellFlags::WIDE_CHAR_SPACER))
+                    .flat_map(Cell::chars)
+                    .collect();
+                line.trim_end().to_string()
+            })
+            .collect();
+        lines.join("\n")
     }
 
-    fn print_vec(&self, v: &[Row], f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn print_vec<'a>(
+        &self,
+        v: impl IntoIterator<Item = &'a Row>,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
         for _ in 0 .. self.columns {
             write!(f, "_")?;
         }
@@ -145,12 +184,8 @@ impl Grid {
         for row in v {
             write!(f, "|")?;
             for cell in &row.inner {
-                if let Some(c) = cell.c {
-                    if c == '\t' {
-                        write!(f, " ")?;
-                    } else {
-                        write!(f, "{}", c)?;
-                    }
+                if !cell.is_empty() {
+                    write!(f, "{}", cell.chars().collect::<String>())?;
                 }
             }
             writeln!(f, "|")?;
@@ -173,13 +208,13 @@ impl Index<usize> for Grid {
     type Output = Row;
 
     fn index(&self, index: usize) -> &Self::Output {
-        &self.rows[index]
+        &self.storage[index]
     }
 }
 
 impl IndexMut<usize> for Grid {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.rows[index]
+        &mut self.storage[index]
     }
 }
 
@@ -187,8 +222,8 @@ impl Iterator for Grid {
     type Item = Row;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.rows.len() {
-            let row = &self.rows[self.index];
+        if self.index < self.lines() {
+            let row = &self.storage[self.index];
             self.index += 1;
             Some(row.clone())
         } else {
@@ -200,9 +235,10 @@ impl Iterator for Grid {
 impl Display for Grid {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "\n\n#################################\n\n")?;
-        self.print_vec(&self.scrollback, f)?;
+        let history = (0 .. self.history_len()).map(|index| self.history(index));
+        self.print_vec(history, f)?;
         writeln!(f, "-------------------------------------")?;
-        self.print_vec(&self.rows, f)?;
+        self.print_vec(self.storage.screen(), f)?;
 
         Ok(())
     }
@@ -246,4 +282,52 @@ mod test {
 
         assert!(g[1][1].c == Some('a'));
     }
+
+    #[test]
+    fn test_save_and_restore_cursor() {
+        let mut g = Grid::new(4, 4);
+        g.restore_cursor();
+        assert_eq!(g.cursor, Cursor::default());
+
+        g.cursor.line = 3;
+        g.cursor.column = 3;
+        g.save_cursor();
+        g.cursor.line = 0;
+        g.resize(2, 2);
+        g.restore_cursor();
+
+        assert_eq!((g.cursor.line, g.cursor.column), (1, 1));
+    }
+
+    #[test]
+    fn test_screen_skips_spacers() {
+        let mut g = Grid::new(4, 1);
+        g.write_char('中');
+        g.write_char('a');
+
+        assert_eq!(g.screen(), "中a");
+        #[allow(deprecated)]
+        let sections = g.sections();
+        assert_eq!(sections[0].text, "中a \n");
+    }
+
+    #[test]
+    fn test_grapheme_clusters_survive_resize() {
+        let mut g = Grid::new(4, 2);
+        for c in "e\u{301}👍🏽".chars() {
+            g.write_char(c);
+        }
+        assert_eq!(g[0][0].zerowidth(), ['\u{301}']);
+
+        g.resize(6, 2);
+        assert_eq!(g.screen(), "e\u{301}👍🏽\n");
+    }
+
+    #[test]
+    fn test_screen() {
+        let mut g = Grid::new(3, 2);
+        g[0][1].c = Some('a');
+
+        assert_eq!(g.screen(), " a\n");
+    }
 }
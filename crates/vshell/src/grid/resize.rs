@@ -0,0 +1,223 @@
+//! Resizing, with lines that wrapped flowing back together or apart.
+
+use std::mem;
+
+use crate::grid::{
+    cell::{Cell, CellFlags, Style},
+    row::Row,
+    Grid,
+};
+
+/// Where the cursor is within a logical line, counted in cells.
+#[derive(Debug, Copy, Clone)]
+struct LineCursor {
+    offset: usize,
+    input_needs_wrap: bool,
+}
+
+impl Grid {
+    /// Rewraps the scrollback and the screen to the new width. Lines that
+    /// don't fit on the screen anymore go to the scrollback, and the cursor
+    /// stays with the text it was at.
+    pub fn resize(&mut self, new_columns: usize, new_lines: usize) {
+        let cursor_row = self.history_len() + self.cursor.line;
+        let mut rows = self.storage.take_rows();
+        // Blank lines below the cursor are padding, not content.
+        while rows.len() > cursor_row + 1 && rows.last().is_some_and(is_blank) {
+            rows.pop();
+        }
+
+        let mut new_rows = Vec::with_capacity(rows.len());
+        let mut new_cursor = (0, 0, false);
+        let mut rows = rows.into_iter().enumerate().peekable();
+        while let Some((index, row)) = rows.next() {
+            // Join the rows of a logical line, dropping the spacers left where
+            // a wide character didn't fit.
+            let mut cells = vec![];
+            let mut cursor = None;
+            let mut next = Some((index, row));
+            while let Some((index, mut row)) = next.take() {
+                if index == cursor_row {
+                    let input_needs_wrap = self.cursor.input_needs_wrap;
+                    let offset = cells.len() + self.cursor.column + input_needs_wrap as usize;
+                    cursor = Some(LineCursor { offset, input_needs_wrap });
+                }
+                let wrapped = row.wrapped && rows.peek().is_some();
+                let spacer = CellFlags::LEADING_WIDE_CHAR_SPACER;
+                if wrapped && row.inner.last().is_some_and(|cell| cell.flags.contains(spacer)) {
+                    row.inner.pop();
+                }
+                cells.append(&mut row.inner);
+                if wrapped {
+                    next = rows.next();
+                }
+            }
+            while cells.last().is_some_and(is_padding) {
+                cells.pop();
+            }
+
+            let first = new_rows.len();
+            let (line, column, input_needs_wrap) =
+                rewrap(cells, new_columns, cursor, &mut new_rows);
+            if cursor.is_some() {
+                new_cursor = (first + line, column, input_needs_wrap);
+            }
+        }
+
+        // Keep the cursor on the screen, even if that cuts off lines below it.
+        let (cursor_row, column, input_needs_wrap) = new_cursor;
+        let history = new_rows.len().saturating_sub(new_lines).min(cursor_row);
+        new_rows.resize(history + new_lines, Row::new(new_columns));
+        self.storage.replace_rows(new_rows, new_lines);
+
+        self.columns = new_columns;
+        self.cursor.line = cursor_row - history;
+        self.cursor.column = column;
+        self.cursor.input_needs_wrap = input_needs_wrap;
+        self.forget_printed();
+        self.scroll_region = 0 .. new_lines;
+        self.margins = 0 .. new_columns;
+        self.tabs.resize(new_columns);
+        self.damage.resize(new_columns, new_lines);
+        self.clamp_cursor();
+    }
+}
+
+fn is_blank(row: &Row) -> bool {
+    !row.wrapped && row.inner.iter().all(is_padding)
+}
+
+/// An erased cell without a background.
+fn is_padding(cell: &Cell) -> bool {
+    cell.is_empty() && cell.style == Style::default()
+}
+
+/// Splits a logical line into rows of `columns` cells, marking all but the
+/// last as wrapped. Returns the cursor's row, relative to the first one, and
+/// column.
+fn rewrap(
+    cells: Vec<Cell>,
+    columns: usize,
+    cursor: Option<LineCursor>,
+    rows: &mut Vec<Row>,
+) -> (usize, usize, bool) {
+    let first = rows.len();
+    let mut row = Row::new(columns);
+    let mut column = 0;
+    let mut new_cursor = None;
+    let len = cells.len();
+    for (index, cell) in cells.into_iter().enumerate() {
+        // Wide characters don't get split at the new edge.
+        let wide = cell.flags.contains(CellFlags::WIDE_CHAR);
+        if column == columns || (wide && column + 1 == columns && column > 0) {
+            if column < columns {
+                row[column].flags = CellFlags::LEADING_WIDE_CHAR_SPACER;
+            }
+            row.wrapped = true;
+            rows.push(mem::replace(&mut row, Row::new(columns)));
+            column = 0;
+        }
+        if cursor.is_some_and(|cursor| cursor.offset == index) {
+            new_cursor = Some((rows.len() - first, column, false));
+        }
+        row[column] = cell;
+        column += 1;
+    }
+
+    // The cursor is past the text, it keeps its distance up to the edge.
+    let new_cursor = match (new_cursor, cursor) {
+        (Some(new_cursor), _) => new_cursor,
+        (None, Some(cursor)) => {
+            let column = column + cursor.offset.saturating_sub(len);
+            let input_needs_wrap =
+                column >= columns && cursor.input_needs_wrap && cursor.offset == len;
+            (rows.len() - first, column.min(columns - 1), input_needs_wrap)
+        }
+        (None, None) => (0, 0, false),
+    };
+    rows.push(row);
+    new_cursor
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::{ClearMode, LineClearMode};
+
+    fn write(g: &mut Grid, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    g.carriage_return();
+                    g.linefeed();
+                }
+                _ => g.write_char(c),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reflow() {
+        let mut g = Grid::new(4, 3);
+        write(&mut g, "abcdef\nxy");
+        assert!(g[0].wrapped);
+        assert_eq!(g.screen(), "abcd\nef\nxy");
+
+        g.resize(8, 3);
+        assert_eq!(g.screen(), "abcdef\nxy\n");
+        assert_eq!((g.cursor.line, g.cursor.column), (1, 2));
+
+        g.resize(3, 3);
+        assert_eq!(g.screen(), "abc\ndef\nxy");
+        assert_eq!((g.cursor.line, g.cursor.column), (2, 2));
+    }
+
+    #[test]
+    fn test_erased_line_ends_dont_reflow() {
+        for erase in [
+            |g: &mut Grid| g.clear_line(LineClearMode::Right),
+            |g: &mut Grid| g.clear_screen(ClearMode::Below),
+            |g: &mut Grid| g.erase_chars(2),
+        ] {
+            let mut g = Grid::new(4, 3);
+            write(&mut g, "abcdef");
+            g.goto(0, 2);
+            erase(&mut g);
+            assert!(!g[0].wrapped);
+
+            g.resize(8, 3);
+            assert!(g.screen().starts_with("ab\n"));
+        }
+    }
+
+    #[test]
+    fn test_overflow_goes_to_scrollback() {
+        let mut g = Grid::new(4, 2);
+        write(&mut g, "abcdefgh");
+        assert_eq!(g.history_len(), 0);
+
+        g.resize(2, 2);
+        assert_eq!(g.history_len(), 2);
+        assert_eq!(g.screen(), "ef\ngh");
+        assert!(g.cursor.input_needs_wrap);
+
+        g.resize(8, 2);
+        assert_eq!(g.history_len(), 0);
+        assert_eq!(g.screen(), "abcdefgh\n");
+        assert_eq!((g.cursor.line, g.cursor.column), (0, 7));
+        assert!(g.cursor.input_needs_wrap);
+    }
+
+    #[test]
+    fn test_wide_chars_at_the_new_edge() {
+        let mut g = Grid::new(4, 2);
+        write(&mut g, "a中b");
+
+        g.resize(2, 3);
+        assert_eq!(g.screen(), "a\n中\nb");
+        assert!(g[0][1].flags.contains(CellFlags::LEADING_WIDE_CHAR_SPACER));
+
+        g.resize(4, 3);
+        assert_eq!(g.screen(), "a中b\n\n");
+    }
+}
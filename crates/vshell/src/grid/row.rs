@@ -3,11 +3,14 @@ use std::{
     slice::Iter,
 };
 
-use crate::grid::cell::Cell;
+use crate::grid::cell::{Cell, CellFlags};
 
 #[derive(Debug, Clone)]
 pub struct Row {
     pub inner: Vec<Cell>,
+    /// The line went on to the next row when the cursor auto-wrapped, so the
+    /// two are rejoined on resize.
+    pub wrapped: bool,
 }
 
 impl Row {
@@ -16,12 +19,26 @@ impl Row {
 
         inner.resize(columns, Cell::default());
 
-        Self { inner }
+        Self { inner, wrapped: false }
     }
 
-    pub fn reset(&mut self) {
-        for cell in &mut self.inner {
-            cell.c = None;
+    /// Overwrites every cell with `blank`.
+    pub fn reset(&mut self, blank: &Cell) {
+        self.inner.fill(blank.clone());
+        self.wrapped = false;
+    }
+
+    /// Blanks both halves of a wide character that straddles the boundary
+    /// left of `column`, before the cells on either side are overwritten or
+    /// moved apart.
+    pub fn split_wide_char(&mut self, column: usize, blank: &Cell) {
+        let straddles = column > 0 &&
+            self.inner
+                .get(column)
+                .is_some_and(|cell| cell.flags.contains(CellFlags::WIDE_CHAR_SPACER));
+        if straddles {
+            self.inner[column - 1] = blank.clone();
+            self.inner[column] = blank.clone();
         }
     }
 }
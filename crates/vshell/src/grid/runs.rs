@@ -0,0 +1,104 @@
+//! Borrowing iteration over the screen for renderers.
+
+use std::ops::Range;
+
+use crate::grid::{
+    cell::{Cell, CellFlags, Style},
+    Grid,
+};
+
+/// Cells next to each other on a line that share a style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleRun<'a> {
+    pub line: usize,
+    pub columns: Range<usize>,
+    pub style: &'a Style,
+}
+
+/// The style runs of the screen, line by line. See [`Grid::style_runs`].
+#[derive(Debug, Clone)]
+pub struct StyleRuns<'a> {
+    grid: &'a Grid,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Iterator for StyleRuns<'a> {
+    type Item = StyleRun<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // A grid without columns has lines, but no runs on them.
+        if self.line >= self.grid.lines() || self.grid.columns() == 0 {
+            return None;
+        }
+
+        let row = &self.grid[self.line].inner;
+        let start = self.column;
+        let style = &row[start].style;
+        let end = row[start ..]
+            .iter()
+            .position(|cell| cell.style != *style)
+            .map_or(row.len(), |len| start + len);
+        let run = StyleRun { line: self.line, columns: start .. end, style };
+
+        if end == row.len() {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column = end;
+        }
+        Some(run)
+    }
+}
+
+impl Grid {
+    /// The screen split into runs of equally styled cells, which never span
+    /// lines. Their cells are in `grid[run.line].inner[run.columns]`.
+    pub fn style_runs(&self) -> StyleRuns<'_> {
+        StyleRuns { grid: self, line: 0, column: 0 }
+    }
+
+    /// The cells of a screen line with their columns, skipping the spacers of
+    /// wide characters.
+    pub fn line_cells(&self, line: usize) -> impl Iterator<Item = (usize, &Cell)> {
+        self[line]
+            .inner
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| !cell.flags.contains(CellFlags::WIDE_CHAR_SPACER))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color::{Color, NamedColor};
+
+    #[test]
+    fn test_style_runs() {
+        let mut g = Grid::new(4, 2);
+        g.write_char('a');
+        g.cursor.template.fg = Color::Named(NamedColor::Red);
+        g.write_char('中');
+        g.write_char('b');
+
+        let runs: Vec<_> =
+            g.style_runs().map(|run| (run.line, run.columns, run.style.fg)).collect();
+        assert_eq!(
+            runs,
+            [
+                (0, 0 .. 1, Color::Default),
+                (0, 1 .. 4, Color::Named(NamedColor::Red)),
+                (1, 0 .. 4, Color::Default),
+            ]
+        );
+
+        let cells: Vec<_> = g.line_cells(0).map(|(column, cell)| (column, cell.c)).collect();
+        assert_eq!(cells, [(0, Some('a')), (1, Some('中')), (3, Some('b'))]);
+    }
+
+    #[test]
+    fn test_style_runs_without_columns() {
+        assert_eq!(Grid::new(0, 2).style_runs().count(), 0);
+    }
+}
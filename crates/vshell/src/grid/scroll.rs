@@ -0,0 +1,253 @@
+//! Scrolling, confined to the scroll region and the left/right margins.
+
+use std::{mem, ops::Range};
+
+use crate::grid::Grid;
+
+impl Grid {
+    /// The lines that scroll, set by DECSTBM.
+    pub fn scroll_region(&self) -> Range<usize> {
+        self.scroll_region.clone()
+    }
+
+    /// The columns that scroll, set by DECSLRM. Always the full width while
+    /// DECLRMM is off.
+    pub fn margins(&self) -> Range<usize> {
+        self.margins.clone()
+    }
+
+    pub fn margin_mode(&self) -> bool {
+        self.margin_mode
+    }
+
+    /// DECSTBM with `top` and `bottom` as a range of lines. Regions of less
+    /// than two lines are ignored, like in xterm.
+    pub fn set_scroll_region(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.lines());
+        if top + 1 >= bottom {
+            return;
+        }
+        self.scroll_region = top .. bottom;
+        self.goto(0, 0);
+    }
+
+    /// DECLRMM, turning it off also resets the margins.
+    pub fn set_margin_mode(&mut self, enabled: bool) {
+        self.margin_mode = enabled;
+        self.margins = 0 .. self.columns;
+    }
+
+    /// DECSLRM with `left` and `right` as a range of columns.
+    pub fn set_margins(&mut self, left: usize, right: usize) {
+        let right = right.min(self.columns);
+        if !self.margin_mode || left + 1 >= right {
+            return;
+        }
+        self.margins = left .. right;
+        self.goto(0, 0);
+    }
+
+    /// Where lines end for the cursor, the right margin unless the cursor is
+    /// already past it.
+    pub fn line_end(&self) -> usize {
+        if self.cursor.column < self.margins.end {
+            self.margins.end
+        } else {
+            self.columns
+        }
+    }
+
+    /// CR, to the left margin unless the cursor is left of it.
+    pub fn carriage_return(&mut self) {
+        let column = if self.cursor.column >= self.margins.start { self.margins.start } else { 0 };
+        self.goto_column(column);
+    }
+
+    /// LF and IND, scrolling the region at its bottom.
+    pub fn linefeed(&mut self) {
+        self.cursor.input_needs_wrap = false;
+        if self.cursor.line + 1 == self.scroll_region.end {
+            self.scroll_up();
+        } else if self.cursor.line + 1 < self.lines() {
+            self.cursor.line += 1;
+        }
+    }
+
+    /// RI, scrolling the region down at its top.
+    pub fn reverse_index(&mut self) {
+        self.cursor.input_needs_wrap = false;
+        if self.cursor.line == self.scroll_region.start {
+            self.scroll_down_lines(1);
+        } else if self.cursor.line > 0 {
+            self.cursor.line -= 1;
+        }
+    }
+
+    /// Scrolls the region up by one.
+    pub fn scroll_up(&mut self) {
+        self.scroll_up_lines(1);
+    }
+
+    /// SU. Only lines leaving the whole screen go to the scrollback, those of
+    /// a smaller region are gone.
+    pub fn scroll_up_lines(&mut self, count: usize) {
+        self.forget_printed();
+        let region = self.scroll_region();
+        let count = count.min(region.len());
+        if region == (0 .. self.lines()) && self.margins == (0 .. self.columns) {
+            let blank = self.blank();
+            self.storage.scroll_up(count, &blank);
+            self.damage.scroll_up(count);
+        } else {
+            self.shift_up(region, count);
+        }
+    }
+
+    /// SD, the lines scrolled in at the top are blank.
+    pub fn scroll_down_lines(&mut self, count: usize) {
+        self.shift_down(self.scroll_region(), count);
+    }
+
+    /// Moves the content of `lines` up by `count` within the margins and
+    /// blanks what is left at the bottom.
+    pub(super) fn shift_up(&mut self, lines: Range<usize>, count: usize) {
+        self.forget_printed();
+        self.damage.damage_lines(lines.clone());
+        let blank = self.blank();
+        let count = count.min(lines.len());
+        let margins = self.margins();
+        if margins == (0 .. self.columns) {
+            for line in lines.start .. lines.end - count {
+                self.storage.swap(line, line + count);
+            }
+            for line in lines.end - count .. lines.end {
+                self.storage[line].reset(&blank);
+            }
+            return;
+        }
+
+        self.split_at_margins(lines.clone());
+        for line in lines.start .. lines.end - count {
+            for column in margins.clone() {
+                self.storage[line][column] = mem::take(&mut self.storage[line + count][column]);
+            }
+        }
+        for line in lines.end - count .. lines.end {
+            self.storage[line].inner[margins.clone()].fill(blank.clone());
+        }
+    }
+
+    /// Moves the content of `lines` down by `count` within the margins and
+    /// blanks what is left at the top.
+    pub(super) fn shift_down(&mut self, lines: Range<usize>, count: usize) {
+        self.forget_printed();
+        self.damage.damage_lines(lines.clone());
+        let blank = self.blank();
+        let count = count.min(lines.len());
+        let margins = self.margins();
+        if margins == (0 .. self.columns) {
+            for line in (lines.start + count .. lines.end).rev() {
+                self.storage.swap(line, line - count);
+            }
+            for line in lines.start .. lines.start + count {
+                self.storage[line].reset(&blank);
+            }
+            return;
+        }
+
+        self.split_at_margins(lines.clone());
+        for line in (lines.start + count .. lines.end).rev() {
+            for column in margins.clone() {
+                self.storage[line][column] = mem::take(&mut self.storage[line - count][column]);
+            }
+        }
+        for line in lines.start .. lines.start + count {
+            self.storage[line].inner[margins.clone()].fill(blank.clone());
+        }
+    }
+
+    /// Blanks the wide characters cut by the margins, before what's between
+    /// them moves on its own.
+    fn split_at_margins(&mut self, lines: Range<usize>) {
+        let blank = self.blank();
+        let margins = self.margins();
+        for line in lines {
+            self.storage[line].split_wide_char(margins.start, &blank);
+            self.storage[line].split_wide_char(margins.end, &blank);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::grid::cell::Cell;
+
+    /// A grid with one letter per line, starting at `a`.
+    fn letters(columns: usize, lines: usize) -> Grid {
+        let mut g = Grid::new(columns, lines);
+        for line in 0 .. lines {
+            g[line].inner.fill(Cell { c: Some((b'a' + line as u8) as char), ..Cell::new() });
+        }
+        g
+    }
+
+    #[test]
+    fn test_full_screen_scrolls_into_scrollback() {
+        let mut g = letters(2, 3);
+        g.scroll_up_lines(2);
+
+        assert_eq!(g.screen(), "cc\n\n");
+        assert_eq!(g.history_len(), 2);
+    }
+
+    #[test]
+    fn test_scrollback_limit() {
+        let mut g = letters(2, 3).with_scrollback_limit(1);
+        g.scroll_up_lines(2);
+
+        assert_eq!(g.history_len(), 1);
+        assert!(g.history(0)[0].c == Some('b'));
+    }
+
+    #[test]
+    fn test_region_scrolls_alone() {
+        let mut g = letters(2, 4);
+        g.set_scroll_region(1, 3);
+        g.goto(2, 0);
+        g.linefeed();
+
+        assert_eq!(g.screen(), "aa\ncc\n\ndd");
+        assert_eq!(g.history_len(), 0);
+
+        g.goto(1, 0);
+        g.reverse_index();
+        assert_eq!(g.screen(), "aa\n\ncc\ndd");
+    }
+
+    #[test]
+    fn test_invalid_region_is_ignored() {
+        let mut g = Grid::new(2, 4);
+        g.set_scroll_region(2, 3);
+        g.set_scroll_region(3, 1);
+
+        assert_eq!(g.scroll_region(), 0 .. 4);
+    }
+
+    #[test]
+    fn test_margins() {
+        let mut g = letters(4, 3);
+        g.set_margins(1, 3);
+        assert_eq!(g.margins(), 0 .. 4);
+
+        g.set_margin_mode(true);
+        g.set_margins(1, 3);
+        g.scroll_up_lines(1);
+        assert_eq!(g.screen(), "abba\nbccb\nc  c");
+        assert_eq!(g.history_len(), 0);
+
+        g.goto(0, 2);
+        g.shift_down(0 .. 3, 1);
+        assert_eq!(g.screen(), "a  a\nbbbb\ncccc");
+    }
+}
@@ -0,0 +1,183 @@
+//! The rows of the scrollback and the screen, kept in one ring buffer.
+
+use std::{
+    mem,
+    ops::{Index, IndexMut},
+};
+
+use crate::grid::{cell::Cell, row::Row};
+
+/// Scrollback and screen rows, oldest first, the screen being the last
+/// `lines` of them.
+///
+/// Scrolling the whole screen only moves where the ring starts. Once the
+/// history is full, its oldest row is reused as the new bottom line, so
+/// nothing is allocated or copied however much output scrolls by.
+#[derive(Debug, Clone)]
+pub struct Storage {
+    inner: Vec<Row>,
+    /// Where the oldest row is in `inner`.
+    zero: usize,
+    lines: usize,
+    /// The most rows kept above the screen.
+    max_history: usize,
+}
+
+impl Storage {
+    pub fn new(columns: usize, lines: usize, max_history: usize) -> Self {
+        Self { inner: vec![Row::new(columns); lines], zero: 0, lines, max_history }
+    }
+
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.inner.len() - self.lines
+    }
+
+    /// Drops the oldest history rows beyond the new limit.
+    pub fn set_max_history(&mut self, max_history: usize) {
+        self.max_history = max_history;
+        self.truncate_history(max_history);
+    }
+
+    pub fn clear_history(&mut self) {
+        self.truncate_history(0);
+    }
+
+    /// A row of the scrollback, 0 being the oldest.
+    pub fn history(&self, index: usize) -> &Row {
+        assert!(index < self.history_len(), "history row {index} out of range");
+        &self.inner[self.physical(index)]
+    }
+
+    /// The rows on the screen, top to bottom.
+    pub fn screen(&self) -> impl DoubleEndedIterator<Item = &Row> {
+        (0 .. self.lines).map(|line| &self[line])
+    }
+
+    pub fn swap(&mut self, a: usize, b: usize) {
+        let a = self.physical(self.history_len() + a);
+        let b = self.physical(self.history_len() + b);
+        self.inner.swap(a, b);
+    }
+
+    /// Scrolls the whole screen up, its top lines becoming history. The lines
+    /// coming in at the bottom are `blank`.
+    pub fn scroll_up(&mut self, count: usize, blank: &Cell) {
+        for _ in 0 .. count {
+            if self.history_len() < self.max_history {
+                // Only ever rotates after the limit was raised or the history
+                // cleared, the ring still starts at 0 while it's growing.
+                self.inner.rotate_left(self.zero);
+                self.zero = 0;
+                let mut row = Row::new(self[0].inner.len());
+                row.reset(blank);
+                self.inner.push(row);
+            } else {
+                self.zero = (self.zero + 1) % self.inner.len();
+                let bottom = self.lines - 1;
+                self[bottom].reset(blank);
+            }
+        }
+    }
+
+    /// Takes out all rows, oldest first, leaving the storage empty.
+    pub fn take_rows(&mut self) -> Vec<Row> {
+        self.inner.rotate_left(self.zero);
+        self.zero = 0;
+        mem::take(&mut self.inner)
+    }
+
+    /// Replaces all rows, the last `lines` of them being the screen. History
+    /// beyond the limit is dropped.
+    pub fn replace_rows(&mut self, rows: Vec<Row>, lines: usize) {
+        assert!(rows.len() >= lines, "fewer rows than lines on the screen");
+        self.inner = rows;
+        self.zero = 0;
+        self.lines = lines;
+        self.truncate_history(self.max_history);
+    }
+
+    fn truncate_history(&mut self, max_history: usize) {
+        let excess = self.history_len().saturating_sub(max_history);
+        if excess > 0 {
+            self.inner.rotate_left(self.zero);
+            self.zero = 0;
+            self.inner.drain(.. excess);
+        }
+    }
+
+    fn physical(&self, index: usize) -> usize {
+        (self.zero + index) % self.inner.len()
+    }
+}
+
+impl Index<usize> for Storage {
+    type Output = Row;
+
+    /// A line of the screen.
+    fn index(&self, line: usize) -> &Row {
+        assert!(line < self.lines, "line {line} out of range");
+        &self.inner[self.physical(self.history_len() + line)]
+    }
+}
+
+impl IndexMut<usize> for Storage {
+    fn index_mut(&mut self, line: usize) -> &mut Row {
+        assert!(line < self.lines, "line {line} out of range");
+        let index = self.physical(self.history_len() + line);
+        &mut self.inner[index]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn letter(row: &Row) -> Option<char> {
+        row[0].c
+    }
+
+    fn scroll_in(storage: &mut Storage, c: char) {
+        storage.scroll_up(1, &Cell::new());
+        let line = storage.lines() - 1;
+        storage[line][0].c = Some(c);
+    }
+
+    #[test]
+    fn test_ring_reuses_rows() {
+        let mut storage = Storage::new(1, 2, 3);
+        for c in 'a' ..= 'z' {
+            scroll_in(&mut storage, c);
+        }
+
+        assert_eq!(storage.inner.len(), 5);
+        assert_eq!(storage.history_len(), 3);
+        let history: Vec<_> = (0 .. 3).map(|index| letter(storage.history(index))).collect();
+        assert_eq!(history, [Some('v'), Some('w'), Some('x')]);
+        let screen: Vec<_> = storage.screen().map(letter).collect();
+        assert_eq!(screen, [Some('y'), Some('z')]);
+    }
+
+    #[test]
+    fn test_limit_changes() {
+        let mut storage = Storage::new(1, 2, 1);
+        for c in 'a' ..= 'e' {
+            scroll_in(&mut storage, c);
+        }
+        storage.set_max_history(3);
+        scroll_in(&mut storage, 'f');
+        assert_eq!(storage.history_len(), 2);
+        assert_eq!(letter(storage.history(0)), Some('c'));
+
+        storage.set_max_history(1);
+        assert_eq!(letter(storage.history(0)), Some('d'));
+        assert_eq!(storage.screen().map(letter).collect::<Vec<_>>(), [Some('e'), Some('f')]);
+
+        storage.clear_history();
+        assert_eq!(storage.history_len(), 0);
+        assert_eq!(storage[0][0].c, Some('e'));
+    }
+}
@@ -0,0 +1,83 @@
+/// Columns between the default tab stops.
+const TAB_WIDTH: usize = 8;
+
+/// One flag per column, set where HT stops.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabStops {
+    stops: Vec<bool>,
+}
+
+impl TabStops {
+    /// A stop every [`TAB_WIDTH`] columns.
+    pub fn new(columns: usize) -> Self {
+        Self { stops: (0 .. columns).map(is_default_stop).collect() }
+    }
+
+    /// Keeps the stops of the remaining columns, new columns get the default
+    /// ones.
+    pub fn resize(&mut self, columns: usize) {
+        let old_columns = self.stops.len();
+        self.stops.truncate(columns);
+        self.stops.extend((old_columns .. columns).map(is_default_stop));
+    }
+
+    pub fn set(&mut self, column: usize) {
+        if let Some(stop) = self.stops.get_mut(column) {
+            *stop = true;
+        }
+    }
+
+    pub fn clear(&mut self, column: usize) {
+        if let Some(stop) = self.stops.get_mut(column) {
+            *stop = false;
+        }
+    }
+
+    pub fn clear_all(&mut self) {
+        self.stops.fill(false);
+    }
+
+    /// The first stop right of `column`.
+    pub fn next(&self, column: usize) -> Option<usize> {
+        (column + 1 .. self.stops.len()).find(|&column| self.stops[column])
+    }
+
+    /// The first stop left of `column`.
+    pub fn prev(&self, column: usize) -> Option<usize> {
+        (0 .. column.min(self.stops.len())).rev().find(|&column| self.stops[column])
+    }
+}
+
+fn is_default_stop(column: usize) -> bool {
+    column > 0 && column.is_multiple_of(TAB_WIDTH)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_stops() {
+        let tabs = TabStops::new(20);
+
+        assert_eq!(tabs.next(0), Some(8));
+        assert_eq!(tabs.next(8), Some(16));
+        assert_eq!(tabs.next(16), None);
+        assert_eq!(tabs.prev(16), Some(8));
+        assert_eq!(tabs.prev(8), None);
+    }
+
+    #[test]
+    fn test_resize_keeps_stops() {
+        let mut tabs = TabStops::new(10);
+        tabs.clear_all();
+        tabs.set(3);
+        tabs.resize(20);
+
+        assert_eq!(tabs.next(0), Some(3));
+        assert_eq!(tabs.next(3), Some(16));
+
+        tabs.resize(2);
+        assert_eq!(tabs.next(0), None);
+    }
+}
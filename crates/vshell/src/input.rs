@@ -1,5 +1,4 @@
-use std::sync::mpsc::Sender;
-
+use vtty::Notifier;
 use winit::{
     event::KeyEvent,
     keyboard::{Key, ModifiersState, NamedKey},
@@ -7,12 +6,12 @@ use winit::{
 
 /// InputState processes input events and sends them to the terminal.
 pub struct InputState {
-    rtx: Sender<Vec<u8>>,
+    notifier: Notifier,
 }
 
 impl InputState {
-    pub fn new(rtx: Sender<Vec<u8>>) -> Self {
-        Self { rtx }
+    pub fn new(notifier: Notifier) -> Self {
+        Self { notifier }
     }
 
     pub fn apply_keyboard(&mut self, input: KeyEvent, mods: &ModifiersState) {
@@ -26,19 +25,23 @@ impl InputState {
             Key::Named(NamedKey::Tab) => Some("\t"),
             Key::Named(NamedKey::Escape) => Some("\x1b"),
             _ => {
+                // Modifiers and other keys without text have nothing to send.
+                let Some(text) = input.logical_key.to_text() else {
+                    return;
+                };
                 if mods.control_key() {
-                    let n = input.logical_key.to_text().unwrap().chars().next().unwrap();
+                    let n = text.chars().next().unwrap_or_default();
                     let mut m = n as u8;
                     m &= 0b1001_1111;
-                    let _ = self.rtx.send(vec![m]);
+                    self.notifier.notify(vec![m]);
                     None
                 } else {
-                    Some(input.logical_key.to_text().unwrap())
+                    Some(text)
                 }
             }
         };
         if let Some(text) = text {
-            let _ = self.rtx.send(text.into());
+            self.notifier.notify(text);
         }
     }
 }
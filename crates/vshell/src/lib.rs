@@ -1,3 +1,10 @@
-mod color;
-mod grid;
-mod input;
+pub mod charset;
+pub mod color;
+pub mod grid;
+pub mod input;
+pub mod parser;
+pub mod replay;
+mod sgr;
+pub mod term;
+
+pub use self::{grid::Grid, term::Term};
@@ -0,0 +1,388 @@
+pub use self::params::{Params, ParamsIter, MAX_PARAMS};
+use self::{
+    table::{Action, State, TABLE},
+    utf8::Utf8Decoder,
+};
+
+mod params;
+mod table;
+mod utf8;
+
+/// Most intermediate bytes a sequence may have, including private markers.
+const MAX_INTERMEDIATES: usize = 2;
+
+/// Most `;` separated parameters of an OSC string. The last one takes the
+/// rest of the string, separators included.
+const MAX_OSC_PARAMS: usize = 16;
+
+/// Longest OSC string that is kept, anything beyond is dropped.
+const MAX_OSC_LEN: usize = 0x10000;
+
+/// Receives the actions the [`Parser`] recognizes in the output of the child.
+///
+/// Every method does nothing by default, so a handler only implements what it
+/// supports.
+pub trait Handler {
+    /// A character to draw at the cursor.
+    fn print(&mut self, _c: char) {}
+
+    /// A C0 control character, like `\n` or BEL.
+    fn execute(&mut self, _byte: u8) {}
+
+    /// `ESC intermediates action`, e.g. `ESC 7` or `ESC ( B`.
+    fn esc_dispatch(&mut self, _intermediates: &[u8], _action: char) {}
+
+    /// `CSI params intermediates action`, e.g. `CSI 1 ; 2 H`. Private markers
+    /// like the `?` of `CSI ? 25 h` are among the intermediates.
+    fn csi_dispatch(&mut self, _params: &Params, _intermediates: &[u8], _action: char) {}
+
+    /// An OSC string split at `;`, e.g. `OSC 0 ; title BEL`.
+    fn osc_dispatch(&mut self, _params: &[&[u8]], _bell_terminated: bool) {}
+
+    /// The start of a DCS string. Its data follows through [`Handler::put`]
+    /// until [`Handler::unhook`].
+    fn hook(&mut self, _params: &Params, _intermediates: &[u8], _action: char) {}
+
+    fn put(&mut self, _byte: u8) {}
+
+    fn unhook(&mut self) {}
+}
+
+/// Turns the output of the child into [`Handler`] actions, following the DEC
+/// ANSI state diagram.
+///
+/// Output may be fed in chunks split anywhere, the parser picks up where the
+/// last chunk ended.
+#[derive(Debug, Default)]
+pub struct Parser {
+    state: State,
+    params: Params,
+    /// The parameter currently being parsed.
+    param: u16,
+    /// Whether any parameter bytes were seen, `CSI m` has no parameters while
+    /// `CSI ; m` has two.
+    has_params: bool,
+    intermediates: [u8; MAX_INTERMEDIATES],
+    intermediates_len: usize,
+    /// Set once a sequence overflows, it is dropped instead of dispatched.
+    ignoring: bool,
+    osc: Vec<u8>,
+    /// Where each OSC parameter but the last ends.
+    osc_ends: [usize; MAX_OSC_PARAMS],
+    osc_ends_len: usize,
+    utf8: Utf8Decoder,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance<H: Handler>(&mut self, handler: &mut H, bytes: &[u8]) {
+        for &byte in bytes {
+            self.advance_byte(handler, byte);
+        }
+    }
+
+    fn advance_byte<H: Handler>(&mut self, handler: &mut H, byte: u8) {
+        // Only the print path continues a multi-byte character, anything else
+        // in between cuts it short.
+        if self.utf8.is_pending() && byte < 0x80 {
+            self.utf8.flush(|c| handler.print(c));
+        }
+
+        let transition = TABLE[self.state as usize][byte as usize];
+        if transition.state.is_some() {
+            self.exit(handler, byte);
+        }
+        self.perform(handler, transition.action, byte);
+        if let Some(state) = transition.state {
+            self.state = state;
+            self.enter(handler, byte);
+        }
+    }
+
+    fn exit<H: Handler>(&mut self, handler: &mut H, byte: u8) {
+        match self.state {
+            State::OscString => self.osc_dispatch(handler, byte == 0x07),
+            State::DcsPassthrough => handler.unhook(),
+            _ => (),
+        }
+    }
+
+    fn enter<H: Handler>(&mut self, handler: &mut H, byte: u8) {
+        match self.state {
+            State::Escape | State::CsiEntry | State::DcsEntry => self.clear(),
+            State::OscString => {
+                self.osc.clear();
+                self.osc_ends_len = 0;
+            }
+            State::DcsPassthrough => {
+                self.finish_params();
+                if self.ignoring {
+                    self.state = State::DcsIgnore;
+                } else {
+                    handler.hook(&self.params, self.intermediates(), byte as char);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn perform<H: Handler>(&mut self, handler: &mut H, action: Action, byte: u8) {
+        match action {
+            Action::None => (),
+            Action::Print if byte < 0x80 => handler.print(byte as char),
+            Action::Print => self.utf8.decode(byte, |c| handler.print(c)),
+            Action::Execute => handler.execute(byte),
+            Action::Collect => {
+                if self.intermediates_len == MAX_INTERMEDIATES {
+                    self.ignoring = true;
+                } else {
+                    self.intermediates[self.intermediates_len] = byte;
+                    self.intermediates_len += 1;
+                }
+            }
+            Action::Param => self.param(byte),
+            Action::EscDispatch => {
+                if !self.ignoring {
+                    handler.esc_dispatch(self.intermediates(), byte as char);
+                }
+            }
+            Action::CsiDispatch => {
+                self.finish_params();
+                if !self.ignoring {
+                    handler.csi_dispatch(&self.params, self.intermediates(), byte as char);
+                }
+            }
+            Action::Put => handler.put(byte),
+            Action::OscPut => {
+                if byte == b';' && self.osc_ends_len < MAX_OSC_PARAMS - 1 {
+                    self.osc_ends[self.osc_ends_len] = self.osc.len();
+                    self.osc_ends_len += 1;
+                } else if self.osc.len() < MAX_OSC_LEN {
+                    self.osc.push(byte);
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.params.clear();
+        self.param = 0;
+        self.has_params = false;
+        self.intermediates_len = 0;
+        self.ignoring = false;
+    }
+
+    fn intermediates(&self) -> &[u8] {
+        &self.intermediates[.. self.intermediates_len]
+    }
+
+    fn param(&mut self, byte: u8) {
+        self.has_params = true;
+        match byte {
+            b';' | b':' if self.params.is_full() => self.ignoring = true,
+            b';' => {
+                self.params.push(self.param);
+                self.param = 0;
+            }
+            b':' => {
+                self.params.extend(self.param);
+                self.param = 0;
+            }
+            _ => {
+                let digit = u16::from(byte - b'0');
+                self.param = self.param.saturating_mul(10).saturating_add(digit);
+            }
+        }
+    }
+
+    /// Adds the last parameter, which has no separator after it.
+    fn finish_params(&mut self) {
+        if !self.has_params || self.ignoring {
+            return;
+        }
+        if self.params.is_full() {
+            self.ignoring = true;
+        } else {
+            self.params.push(self.param);
+        }
+    }
+
+    fn osc_dispatch<H: Handler>(&mut self, handler: &mut H, bell_terminated: bool) {
+        let mut params: [&[u8]; MAX_OSC_PARAMS] = [&[]; MAX_OSC_PARAMS];
+        let mut start = 0;
+        for (param, &end) in params.iter_mut().zip(&self.osc_ends[.. self.osc_ends_len]) {
+            *param = &self.osc[start .. end];
+            start = end;
+        }
+        params[self.osc_ends_len] = &self.osc[start ..];
+
+        handler.osc_dispatch(&params[..= self.osc_ends_len], bell_terminated);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Records every action as a line of text.
+    #[derive(Default)]
+    struct Log(Vec<String>);
+
+    impl Handler for Log {
+        fn print(&mut self, c: char) {
+            self.0.push(format!("print {c}"));
+        }
+
+        fn execute(&mut self, byte: u8) {
+            self.0.push(format!("execute {byte:#04x}"));
+        }
+
+        fn esc_dispatch(&mut self, intermediates: &[u8], action: char) {
+            self.0.push(format!("esc {} {action}", String::from_utf8_lossy(intermediates)));
+        }
+
+        fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], action: char) {
+            let params: Vec<_> = params.iter().collect();
+            let intermediates = String::from_utf8_lossy(intermediates);
+            self.0.push(format!("csi {params:?} {intermediates} {action}"));
+        }
+
+        fn osc_dispatch(&mut self, params: &[&[u8]], bell_terminated: bool) {
+            let params: Vec<_> = params.iter().map(|p| String::from_utf8_lossy(p)).collect();
+            self.0.push(format!("osc {params:?} {bell_terminated}"));
+        }
+
+        fn hook(&mut self, params: &Params, intermediates: &[u8], action: char) {
+            let params: Vec<_> = params.iter().collect();
+            let intermediates = String::from_utf8_lossy(intermediates);
+            self.0.push(format!("hook {params:?} {intermediates} {action}"));
+        }
+
+        fn put(&mut self, byte: u8) {
+            self.0.push(format!("put {}", byte as char));
+        }
+
+        fn unhook(&mut self) {
+            self.0.push("unhook".into());
+        }
+    }
+
+    fn parse(bytes: &[u8]) -> Vec<String> {
+        let mut log = Log::default();
+        Parser::new().advance(&mut log, bytes);
+        log.0
+    }
+
+    #[test]
+    fn test_print_and_execute() {
+        assert_eq!(parse(b"a\r\n"), ["print a", "execute 0x0d", "execute 0x0a"]);
+    }
+
+    #[test]
+    fn test_csi() {
+        assert_eq!(parse(b"\x1b[m"), ["csi []  m"]);
+        assert_eq!(parse(b"\x1b[1;;3H"), ["csi [[1], [0], [3]]  H"]);
+        assert_eq!(parse(b"\x1b[?1049h"), ["csi [[1049]] ? h"]);
+        assert_eq!(parse(b"\x1b[38:2::1:2:3m"), ["csi [[38, 2, 0, 1, 2, 3]]  m"]);
+        assert_eq!(parse(b"\x1b[2 q"), ["csi [[2]]   q"]);
+        assert_eq!(parse(b"\x1b[99999999A"), ["csi [[65535]]  A"]);
+    }
+
+    #[test]
+    fn test_csi_execute_in_between() {
+        assert_eq!(parse(b"\x1b[1\n2H"), ["execute 0x0a", "csi [[12]]  H"]);
+    }
+
+    #[test]
+    fn test_oversized_sequences_are_ignored() {
+        let mut bytes = b"\x1b[".to_vec();
+        bytes.extend(b"1;".repeat(1000));
+        bytes.extend(b"mx");
+        assert_eq!(parse(&bytes), ["print x"]);
+
+        assert_eq!(parse(b"\x1b[1;2?3mx"), ["print x"]);
+        assert_eq!(parse(b"\x1b[?!!!px"), ["print x"]);
+        assert_eq!(parse(b"\x1b(((Bx"), ["print x"]);
+    }
+
+    #[test]
+    fn test_esc() {
+        assert_eq!(parse(b"\x1b7"), ["esc  7"]);
+        assert_eq!(parse(b"\x1b(B"), ["esc ( B"]);
+        assert_eq!(parse(b"\x1b\x1b[A"), ["csi []  A"]);
+        assert_eq!(parse(b"\x1b[1\x18A"), ["execute 0x18", "print A"]);
+    }
+
+    #[test]
+    fn test_osc() {
+        assert_eq!(parse(b"\x1b]0;title\x07"), [r#"osc ["0", "title"] true"#]);
+        assert_eq!(parse(b"\x1b]2;a;b\x1b\\"), [r#"osc ["2", "a", "b"] false"#, "esc  \\"]);
+        assert_eq!(parse(b"\x1b]0;t\xc3\xa9\x07"), [r#"osc ["0", "té"] true"#]);
+
+        let long = [b"\x1b]0;".to_vec(), vec![b'a'; MAX_OSC_LEN * 2], b"\x07".to_vec()].concat();
+        assert_eq!(parse(&long).len(), 1);
+        let many = [b"\x1b]".to_vec(), b";".repeat(100), b"\x07".to_vec()].concat();
+        assert_eq!(parse(&many).len(), 1);
+    }
+
+    #[test]
+    fn test_dcs() {
+        assert_eq!(parse(b"\x1bP1$qm\x1b\\"), ["hook [[1]] $ q", "put m", "unhook", "esc  \\"]);
+    }
+
+    #[test]
+    fn test_sos_pm_apc_are_ignored() {
+        assert_eq!(
+            parse(b"\x1b_apc\x1b\\\x1b^pm\x1b\\\x1bXsos\x1b\\x"),
+            ["esc  \\", "esc  \\", "esc  \\", "print x"]
+        );
+    }
+
+    #[test]
+    fn test_utf8() {
+        assert_eq!(parse("é€😀".as_bytes()), ["print é", "print €", "print 😀"]);
+        assert_eq!(parse(b"\xc3a\xff"), ["print \u{fffd}", "print a", "print \u{fffd}"]);
+    }
+
+    #[test]
+    fn test_chunk_boundaries() {
+        let bytes = "a\x1b[1;2Hé\x1b]0;title\x07\x1bP1$qm\x1b\\😀".as_bytes();
+        let whole = parse(bytes);
+
+        for split in 0 ..= bytes.len() {
+            let mut log = Log::default();
+            let mut parser = Parser::new();
+            parser.advance(&mut log, &bytes[.. split]);
+            parser.advance(&mut log, &bytes[split ..]);
+            assert_eq!(log.0, whole, "split at {split}");
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_print_matches_from_utf8_lossy(
+            bytes in prop::collection::vec(prop_oneof![0x20u8 .. 0x7f, 0x80u8 ..= 0xff], 0 .. 64),
+            split in any::<prop::sample::Index>(),
+        ) {
+            let (head, tail) = bytes.split_at(split.index(bytes.len() + 1));
+            let mut log = Log::default();
+            let mut parser = Parser::new();
+            parser.advance(&mut log, head);
+            parser.advance(&mut log, tail);
+            // Flushes a sequence left pending at the end.
+            parser.advance(&mut log, b"\n");
+
+            let expected: Vec<_> = String::from_utf8_lossy(&bytes)
+                .chars()
+                .map(|c| format!("print {c}"))
+                .chain(["execute 0x0a".to_string()])
+                .collect();
+            prop_assert_eq!(log.0, expected);
+        }
+    }
+}
@@ -0,0 +1,84 @@
+/// Most parameters a sequence may have. Longer lists get the whole sequence
+/// ignored, no real sequence comes close.
+pub const MAX_PARAMS: usize = 32;
+
+/// The numeric parameters of a CSI or DCS sequence.
+///
+/// Parameters are separated by `;`. Each one may be split further into
+/// subparameters with `:`, as in `38:2::255:0:0`. Missing values are 0.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Params {
+    values: [u16; MAX_PARAMS],
+    /// How many values belong to the parameter starting at each index.
+    groups: [u8; MAX_PARAMS],
+    len: usize,
+    /// Where the parameter currently being parsed starts.
+    group_start: usize,
+}
+
+impl Params {
+    /// The number of values, counting subparameters.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == MAX_PARAMS
+    }
+
+    /// Iterates over the parameters, each with its subparameters.
+    pub fn iter(&self) -> ParamsIter<'_> {
+        ParamsIter { params: self, index: 0 }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+        self.group_start = 0;
+    }
+
+    /// Adds a value that ends the current parameter.
+    pub(crate) fn push(&mut self, value: u16) {
+        self.extend(value);
+        self.group_start = self.len;
+    }
+
+    /// Adds a value as a subparameter of the current parameter.
+    pub(crate) fn extend(&mut self, value: u16) {
+        self.values[self.len] = value;
+        self.len += 1;
+        self.groups[self.group_start] = (self.len - self.group_start) as u8;
+    }
+}
+
+impl<'a> IntoIterator for &'a Params {
+    type Item = &'a [u16];
+    type IntoIter = ParamsIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[derive(Debug)]
+pub struct ParamsIter<'a> {
+    params: &'a Params,
+    index: usize,
+}
+
+impl<'a> Iterator for ParamsIter<'a> {
+    type Item = &'a [u16];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.params.len {
+            return None;
+        }
+        let len = self.params.groups[self.index] as usize;
+        let group = &self.params.values[self.index .. self.index + len];
+        self.index += len;
+        Some(group)
+    }
+}
@@ -0,0 +1,184 @@
+//! The state transitions of the DEC ANSI parser, after Paul Williams' state
+//! diagram at <https://vt100.net/emu/dec_ansi_parser>.
+//!
+//! The diagram is written out in [`transition`] and evaluated into [`TABLE`]
+//! at compile time, so the parser only does a lookup per byte.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum State {
+    #[default]
+    Ground,
+    Escape,
+    EscapeIntermediate,
+    CsiEntry,
+    CsiParam,
+    CsiIntermediate,
+    CsiIgnore,
+    DcsEntry,
+    DcsParam,
+    DcsIntermediate,
+    DcsPassthrough,
+    DcsIgnore,
+    OscString,
+    SosPmApcString,
+}
+
+const STATES: [State; 14] = [
+    State::Ground,
+    State::Escape,
+    State::EscapeIntermediate,
+    State::CsiEntry,
+    State::CsiParam,
+    State::CsiIntermediate,
+    State::CsiIgnore,
+    State::DcsEntry,
+    State::DcsParam,
+    State::DcsIntermediate,
+    State::DcsPassthrough,
+    State::DcsIgnore,
+    State::OscString,
+    State::SosPmApcString,
+];
+
+/// What to do with a byte, besides the entry and exit actions of the states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    None,
+    Print,
+    Execute,
+    Collect,
+    Param,
+    EscDispatch,
+    CsiDispatch,
+    Put,
+    OscPut,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub action: Action,
+    /// Set when the byte leaves the state, even if it enters the same one
+    /// again. Entering runs the entry action of the new state.
+    pub state: Option<State>,
+}
+
+pub static TABLE: [[Transition; 256]; STATES.len()] = build();
+
+const fn build() -> [[Transition; 256]; STATES.len()] {
+    let mut table = [[Transition { action: Action::None, state: None }; 256]; STATES.len()];
+    let mut state = 0;
+    while state < STATES.len() {
+        let mut byte = 0;
+        while byte < 256 {
+            table[state][byte] = transition(STATES[state], byte as u8);
+            byte += 1;
+        }
+        state += 1;
+    }
+    table
+}
+
+const fn stay(action: Action) -> Transition {
+    Transition { action, state: None }
+}
+
+const fn to(state: State, action: Action) -> Transition {
+    Transition { action, state: Some(state) }
+}
+
+const fn transition(state: State, byte: u8) -> Transition {
+    use Action::*;
+
+    // Transitions from anywhere. The 8-bit C1 controls are left out, their
+    // bytes are part of UTF-8 sequences.
+    match byte {
+        0x18 | 0x1a => return to(State::Ground, Execute),
+        0x1b => return to(State::Escape, None),
+        _ => (),
+    }
+
+    let c0 = matches!(byte, 0x00 ..= 0x17 | 0x19 | 0x1c ..= 0x1f);
+    match state {
+        State::Ground => match byte {
+            _ if c0 => stay(Execute),
+            0x20 ..= 0x7e | 0x80 ..= 0xff => stay(Print),
+            _ => stay(None),
+        },
+        State::Escape => match byte {
+            _ if c0 => stay(Execute),
+            0x20 ..= 0x2f => to(State::EscapeIntermediate, Collect),
+            b'P' => to(State::DcsEntry, None),
+            b'X' | b'^' | b'_' => to(State::SosPmApcString, None),
+            b'[' => to(State::CsiEntry, None),
+            b']' => to(State::OscString, None),
+            0x30 ..= 0x7e => to(State::Ground, EscDispatch),
+            _ => stay(None),
+        },
+        State::EscapeIntermediate => match byte {
+            _ if c0 => stay(Execute),
+            0x20 ..= 0x2f => stay(Collect),
+            0x30 ..= 0x7e => to(State::Ground, EscDispatch),
+            _ => stay(None),
+        },
+        State::CsiEntry => match byte {
+            _ if c0 => stay(Execute),
+            0x20 ..= 0x2f => to(State::CsiIntermediate, Collect),
+            0x30 ..= 0x3b => to(State::CsiParam, Param),
+            0x3c ..= 0x3f => to(State::CsiParam, Collect),
+            0x40 ..= 0x7e => to(State::Ground, CsiDispatch),
+            _ => stay(None),
+        },
+        State::CsiParam => match byte {
+            _ if c0 => stay(Execute),
+            0x20 ..= 0x2f => to(State::CsiIntermediate, Collect),
+            0x30 ..= 0x3b => stay(Param),
+            0x3c ..= 0x3f => to(State::CsiIgnore, None),
+            0x40 ..= 0x7e => to(State::Ground, CsiDispatch),
+            _ => stay(None),
+        },
+        State::CsiIntermediate => match byte {
+            _ if c0 => stay(Execute),
+            0x20 ..= 0x2f => stay(Collect),
+            0x30 ..= 0x3f => to(State::CsiIgnore, None),
+            0x40 ..= 0x7e => to(State::Ground, CsiDispatch),
+            _ => stay(None),
+        },
+        State::CsiIgnore => match byte {
+            _ if c0 => stay(Execute),
+            0x40 ..= 0x7e => to(State::Ground, None),
+            _ => stay(None),
+        },
+        State::DcsEntry => match byte {
+            0x20 ..= 0x2f => to(State::DcsIntermediate, Collect),
+            0x30 ..= 0x3b => to(State::DcsParam, Param),
+            0x3c ..= 0x3f => to(State::DcsParam, Collect),
+            0x40 ..= 0x7e => to(State::DcsPassthrough, None),
+            _ => stay(None),
+        },
+        State::DcsParam => match byte {
+            0x20 ..= 0x2f => to(State::DcsIntermediate, Collect),
+            0x30 ..= 0x3b => stay(Param),
+            0x3c ..= 0x3f => to(State::DcsIgnore, None),
+            0x40 ..= 0x7e => to(State::DcsPassthrough, None),
+            _ => stay(None),
+        },
+        State::DcsIntermediate => match byte {
+            0x20 ..= 0x2f => stay(Collect),
+            0x30 ..= 0x3f => to(State::DcsIgnore, None),
+            0x40 ..= 0x7e => to(State::DcsPassthrough, None),
+            _ => stay(None),
+        },
+        State::DcsPassthrough => match byte {
+            0x7f => stay(None),
+            _ => stay(Put),
+        },
+        State::DcsIgnore => stay(None),
+        State::OscString => match byte {
+            0x07 => to(State::Ground, None),
+            0x20 ..= 0xff => stay(OscPut),
+            _ => stay(None),
+        },
+        State::SosPmApcString => stay(None),
+    }
+}
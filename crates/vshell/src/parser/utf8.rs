@@ -0,0 +1,139 @@
+/// Decodes UTF-8 one byte at a time, so sequences may be split across reads.
+///
+/// Invalid input is replaced the way the WHATWG Encoding Standard does it:
+/// every maximal subpart of an ill-formed sequence becomes a single U+FFFD,
+/// the same as [`String::from_utf8_lossy`].
+#[derive(Debug, Clone, Copy)]
+pub struct Utf8Decoder {
+    code_point: u32,
+    needed: u8,
+    seen: u8,
+    /// The range of the next continuation byte, narrower after some leading
+    /// bytes to rule out overlong forms, surrogates and values past U+10FFFF.
+    lower: u8,
+    upper: u8,
+}
+
+impl Default for Utf8Decoder {
+    fn default() -> Self {
+        Self { code_point: 0, needed: 0, seen: 0, lower: 0x80, upper: 0xbf }
+    }
+}
+
+impl Utf8Decoder {
+    /// Whether a sequence was started but not completed yet.
+    pub fn is_pending(&self) -> bool {
+        self.needed > 0
+    }
+
+    /// Feeds one byte, emitting up to two characters: the replacement for a
+    /// sequence `byte` cut short, and whatever `byte` completes itself.
+    pub fn decode<F: FnMut(char)>(&mut self, byte: u8, mut emit: F) {
+        if self.needed == 0 {
+            match byte {
+                0x00 ..= 0x7f => emit(byte as char),
+                0xc2 ..= 0xdf => self.start(1, byte & 0x1f),
+                0xe0 ..= 0xef => {
+                    match byte {
+                        0xe0 => self.lower = 0xa0,
+                        0xed => self.upper = 0x9f,
+                        _ => (),
+                    }
+                    self.start(2, byte & 0x0f);
+                }
+                0xf0 ..= 0xf4 => {
+                    match byte {
+                        0xf0 => self.lower = 0x90,
+                        0xf4 => self.upper = 0x8f,
+                        _ => (),
+                    }
+                    self.start(3, byte & 0x07);
+                }
+                _ => emit(char::REPLACEMENT_CHARACTER),
+            }
+            return;
+        }
+
+        if !(self.lower ..= self.upper).contains(&byte) {
+            *self = Self::default();
+            emit(char::REPLACEMENT_CHARACTER);
+            return self.decode(byte, emit);
+        }
+
+        self.lower = 0x80;
+        self.upper = 0xbf;
+        self.code_point = (self.code_point << 6) | u32::from(byte & 0x3f);
+        self.seen += 1;
+        if self.seen == self.needed {
+            let c = char::from_u32(self.code_point).unwrap_or(char::REPLACEMENT_CHARACTER);
+            *self = Self::default();
+            emit(c);
+        }
+    }
+
+    /// Ends a pending sequence, e.g. when an escape sequence interrupts it.
+    pub fn flush<F: FnMut(char)>(&mut self, mut emit: F) {
+        if self.is_pending() {
+            *self = Self::default();
+            emit(char::REPLACEMENT_CHARACTER);
+        }
+    }
+
+    fn start(&mut self, needed: u8, bits: u8) {
+        self.needed = needed;
+        self.code_point = u32::from(bits);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn decode_chunks(chunks: &[&[u8]]) -> String {
+        let mut decoder = Utf8Decoder::default();
+        let mut text = String::new();
+        for chunk in chunks {
+            for &byte in *chunk {
+                decoder.decode(byte, |c| text.push(c));
+            }
+        }
+        decoder.flush(|c| text.push(c));
+        text
+    }
+
+    #[test]
+    fn test_maximal_subparts() {
+        // The examples from the Unicode standard, table 3-8.
+        let bytes = b"\x61\xf1\x80\x80\xe1\x80\xc2\x62\x80\x63\x80\xbf\x64";
+        assert_eq!(decode_chunks(&[bytes]), "a\u{fffd}\u{fffd}\u{fffd}b\u{fffd}c\u{fffd}\u{fffd}d");
+
+        assert_eq!(decode_chunks(&[b"\xe0\x80"]), "\u{fffd}\u{fffd}");
+        assert_eq!(decode_chunks(&[b"\xed\xa0\x80"]), "\u{fffd}\u{fffd}\u{fffd}");
+        assert_eq!(decode_chunks(&[b"\xf4\x90\x80\x80"]), "\u{fffd}".repeat(4));
+        assert_eq!(decode_chunks(&[b"\xf0\x9f", b"\x98", b"\x80"]), "😀");
+    }
+
+    proptest! {
+        #[test]
+        fn test_matches_from_utf8_lossy(
+            // Mostly high bytes, so that sequences are likely to get started.
+            bytes in prop::collection::vec(prop_oneof![any::<u8>(), 0x80u8 ..= 0xf4], 0 .. 64),
+            split in any::<prop::sample::Index>(),
+        ) {
+            let split = split.index(bytes.len() + 1);
+            let (head, tail) = bytes.split_at(split);
+
+            prop_assert_eq!(decode_chunks(&[head, tail]), String::from_utf8_lossy(&bytes));
+        }
+
+        #[test]
+        fn test_valid_text_survives_any_split(text in ".*", split in any::<prop::sample::Index>()) {
+            let bytes = text.as_bytes();
+            let (head, tail) = bytes.split_at(split.index(bytes.len() + 1));
+
+            prop_assert_eq!(decode_chunks(&[head, tail]), text);
+        }
+    }
+}
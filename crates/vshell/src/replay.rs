@@ -0,0 +1,130 @@
+use std::{
+    io,
+    io::BufRead,
+    thread,
+    time::{Duration, Instant},
+};
+
+use vtty::asciicast::{EventKind, Reader};
+
+use crate::term::Term;
+
+/// How fast to replay a recording.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pacing {
+    /// Feeds all events at once, for tests.
+    Instant,
+    /// Waits between events as in the recording, scaled by a speed factor.
+    RealTime(f64),
+}
+
+impl Pacing {
+    /// Whether `speed` works for [`Pacing::RealTime`], it has to be finite
+    /// and positive.
+    pub fn is_valid_speed(speed: f64) -> bool {
+        speed.is_finite() && speed > 0.0
+    }
+}
+
+/// Replays the output of an asciicast recording into a new terminal.
+///
+/// Input events are skipped, the output they caused is part of the recording
+/// already. `on_event` is called after each event, e.g. to show the screen
+/// while replaying.
+pub fn replay<R, F>(reader: Reader<R>, pacing: Pacing, mut on_event: F) -> io::Result<Term>
+where
+    R: BufRead,
+    F: FnMut(&Term),
+{
+    if let Pacing::RealTime(speed) = pacing {
+        if !Pacing::is_valid_speed(speed) {
+            let msg = format!("invalid replay speed {speed}");
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+    }
+
+    let header = reader.header();
+    let (columns, lines) = checked_size(header.width.into(), header.height.into())?;
+    let mut term = Term::new(columns, lines);
+    let start = Instant::now();
+
+    for event in reader {
+        let event = event?;
+        if let Pacing::RealTime(speed) = pacing {
+            let due = Duration::try_from_secs_f64(event.time.as_secs_f64() / speed)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            if let Some(delay) = due.checked_sub(start.elapsed()) {
+                thread::sleep(delay);
+            }
+        }
+
+        match event.kind {
+            EventKind::Output(data) => term.advance(data.as_bytes()),
+            EventKind::Resize { columns, lines } => {
+                let (columns, lines) = checked_size(columns.into(), lines.into())?;
+                term.resize(columns, lines);
+            }
+            EventKind::Input(_) | EventKind::Other(..) => continue,
+        }
+        on_event(&term);
+    }
+
+    Ok(term)
+}
+
+/// A terminal needs at least one cell, a recording may claim otherwise.
+fn checked_size(columns: usize, lines: usize) -> io::Result<(usize, usize)> {
+    if columns == 0 || lines == 0 {
+        let msg = format!("invalid terminal size {columns}x{lines}");
+        return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+    }
+    Ok((columns, lines))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RECORDING: &str = r#"{"version": 2, "width": 10, "height": 3}
+[0.1, "o", "$ ls\r\n"]
+[0.2, "i", "q"]
+[0.3, "o", "a  b\r\n$ "]
+"#;
+
+    #[test]
+    fn test_replay() {
+        let reader = Reader::new(RECORDING.as_bytes()).unwrap();
+        let mut events = 0;
+        let term = replay(reader, Pacing::Instant, |_| events += 1).unwrap();
+
+        assert_eq!(events, 2);
+        assert_eq!(term.grid().screen(), "$ ls\na  b\n$");
+    }
+
+    #[test]
+    fn test_replay_invalid_speed() {
+        for speed in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let reader = Reader::new(RECORDING.as_bytes()).unwrap();
+            let err = replay(reader, Pacing::RealTime(speed), |_| ()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+
+        // Valid, but too slow for the delays to fit into a Duration.
+        let reader = Reader::new(RECORDING.as_bytes()).unwrap();
+        let err = replay(reader, Pacing::RealTime(1e-300), |_| ()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_replay_zero_size() {
+        let recordings = [
+            "{\"version\": 2, \"width\": 0, \"height\": 3}\n",
+            "{\"version\": 2, \"width\": 10, \"height\": 3}\n[0.1, \"r\", \"10x0\"]\n",
+        ];
+        for recording in recordings {
+            let reader = Reader::new(recording.as_bytes()).unwrap();
+            let err = replay(reader, Pacing::Instant, |_| ()).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Select Graphic Rendition, `CSI Pm m`.
+
+use crate::{
+    color::{Color, NamedColor, Rgb},
+    grid::cell::{Flags, Style, Underline},
+    parser::{Params, ParamsIter},
+};
+
+/// Applies an SGR sequence to the cursor's template style.
+pub(crate) fn apply(style: &mut Style, params: &Params) {
+    if params.is_empty() {
+        *style = Style::default();
+        return;
+    }
+
+    let mut params = params.iter();
+    while let Some(param) = params.next() {
+        match param[0] {
+            0 => *style = Style::default(),
+            1 => style.flags.insert(Flags::BOLD),
+            2 => style.flags.insert(Flags::DIM),
+            3 => style.flags.insert(Flags::ITALIC),
+            4 => style.underline = underline(param.get(1).copied().unwrap_or(1)),
+            5 | 6 => style.flags.insert(Flags::BLINK),
+            7 => style.flags.insert(Flags::INVERSE),
+            8 => style.flags.insert(Flags::HIDDEN),
+            9 => style.flags.insert(Flags::STRIKETHROUGH),
+            21 => style.underline = Underline::Double,
+            22 => style.flags.remove(Flags::BOLD | Flags::DIM),
+            23 => style.flags.remove(Flags::ITALIC),
+            24 => style.underline = Underline::None,
+            25 => style.flags.remove(Flags::BLINK),
+            27 => style.flags.remove(Flags::INVERSE),
+            28 => style.flags.remove(Flags::HIDDEN),
+            29 => style.flags.remove(Flags::STRIKETHROUGH),
+            30 ..= 37 => style.fg = named(param[0] - 30),
+            38 => style.fg = extended_color(param, &mut params).unwrap_or(style.fg),
+            39 => style.fg = Color::Default,
+            40 ..= 47 => style.bg = named(param[0] - 40),
+            48 => style.bg = extended_color(param, &mut params).unwrap_or(style.bg),
+            49 => style.bg = Color::Default,
+            53 => style.flags.insert(Flags::OVERLINE),
+            55 => style.flags.remove(Flags::OVERLINE),
+            58 => {
+                let color = extended_color(param, &mut params);
+                style.underline_color = color.unwrap_or(style.underline_color);
+            }
+            59 => style.underline_color = Color::Default,
+            90 ..= 97 => style.fg = named(param[0] - 90 + 8),
+            100 ..= 107 => style.bg = named(param[0] - 100 + 8),
+            _ => (),
+        }
+    }
+}
+
+fn named(index: u16) -> Color {
+    NamedColor::from_index(index as u8).map_or(Color::Default, Color::Named)
+}
+
+/// The styles of `4:n`, as introduced by kitty.
+fn underline(style: u16) -> Underline {
+    match style {
+        0 => Underline::None,
+        2 => Underline::Double,
+        3 => Underline::Curly,
+        4 => Underline::Dotted,
+        5 => Underline::Dashed,
+        _ => Underline::Single,
+    }
+}
+
+/// Parses the colour of SGR 38, 48 and 58. The ITU form puts everything in
+/// subparameters, `38:2::r:g:b` or `38:5:n`, with the colour space id of the
+/// former often left out. The older xterm form spreads it over the following
+/// parameters, `38;2;r;g;b` or `38;5;n`, which are consumed either way.
+fn extended_color(param: &[u16], rest: &mut ParamsIter<'_>) -> Option<Color> {
+    let (kind, values) = if param.len() > 1 {
+        match *param {
+            [_, 2, _, r, g, b, ..] | [_, 2, r, g, b] => (2, [r, g, b]),
+            [_, 5, index, ..] => (5, [index, 0, 0]),
+            _ => return None,
+        }
+    } else {
+        match rest.next()?[0] {
+            2 => (2, [rest.next()?[0], rest.next()?[0], rest.next()?[0]]),
+            5 => (5, [rest.next()?[0], 0, 0]),
+            _ => return None,
+        }
+    };
+
+    let byte = |value: u16| u8::try_from(value).ok();
+    match kind {
+        2 => Some(Color::Rgb(Rgb::from_rgb(byte(values[0])?, byte(values[1])?, byte(values[2])?))),
+        _ => Some(Color::Indexed(byte(values[0])?)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parser::{Handler, Parser};
+
+    /// Runs an SGR sequence through the parser and applies it to `style`.
+    fn sgr(style: &mut Style, sequence: &str) {
+        struct Sgr<'a>(&'a mut Style);
+
+        impl Handler for Sgr<'_> {
+            fn csi_dispatch(&mut self, params: &Params, _: &[u8], action: char) {
+                assert_eq!(action, 'm');
+                apply(self.0, params);
+            }
+        }
+
+        Parser::new().advance(&mut Sgr(style), format!("\x1b[{sequence}m").as_bytes());
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut style = Style::default();
+        sgr(&mut style, "1;2;3;5;7;8;9;53");
+        assert!(style.flags.is_all());
+
+        sgr(&mut style, "22;23;25;27;28;29;55");
+        assert!(style.flags.is_empty());
+
+        sgr(&mut style, "1;4:3");
+        assert_eq!(style.underline, Underline::Curly);
+        sgr(&mut style, "");
+        assert_eq!(style, Style::default());
+    }
+
+    #[test]
+    fn test_colors() {
+        let mut style = Style::default();
+        sgr(&mut style, "31;102");
+        assert_eq!(style.fg, Color::Named(NamedColor::Red));
+        assert_eq!(style.bg, Color::Named(NamedColor::BrightGreen));
+
+        sgr(&mut style, "38;5;208;48;2;1;2;3;1");
+        assert_eq!(style.fg, Color::Indexed(208));
+        assert_eq!(style.bg, Color::Rgb(Rgb::from_rgb(1, 2, 3)));
+        assert!(style.flags.contains(Flags::BOLD));
+
+        sgr(&mut style, "38:2::4:5:6;48:5:17;58:2:7:8:9");
+        assert_eq!(style.fg, Color::Rgb(Rgb::from_rgb(4, 5, 6)));
+        assert_eq!(style.bg, Color::Indexed(17));
+        assert_eq!(style.underline_color, Color::Rgb(Rgb::from_rgb(7, 8, 9)));
+
+        sgr(&mut style, "39;49;59");
+        assert_eq!((style.fg, style.bg, style.underline_color), Default::default());
+    }
+
+    #[test]
+    fn test_invalid_colors_are_skipped() {
+        let mut style = Style::default();
+        sgr(&mut style, "38;5;300;1");
+        assert_eq!(style.fg, Color::Default);
+        assert!(style.flags.contains(Flags::BOLD));
+
+        sgr(&mut style, "38;2;1;2");
+        sgr(&mut style, "38:9:1");
+        assert_eq!(style.fg, Color::Default);
+    }
+}
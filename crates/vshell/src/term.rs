@@ -0,0 +1,532 @@
+use std::mem;
+
+use crate::{
+    charset::{Charset, CharsetIndex},
+    grid::{cursor::CursorStyle, ClearMode, Grid, LineClearMode},
+    parser::{Handler, Params, Parser},
+    sgr,
+};
+
+/// The state of a terminal, fed with the raw output of the child process.
+///
+/// Text, C0 controls, SGR and the CSI sequences for moving the cursor and
+/// editing the screen are interpreted so far.
+#[derive(Debug)]
+pub struct Term {
+    parser: Parser,
+    /// The active screen, the primary one or the alternate one.
+    grid: Grid,
+    inactive_grid: Grid,
+    alt_screen: bool,
+    cursor_style: CursorStyle,
+    /// DECTCEM, applications hide the cursor while they redraw.
+    cursor_visible: bool,
+    /// The last printed character, repeated by REP.
+    last_char: Option<char>,
+}
+
+impl Term {
+    pub fn new(columns: usize, lines: usize) -> Self {
+        Self {
+            parser: Parser::new(),
+            grid: Grid::new(columns, lines),
+            inactive_grid: Grid::new(columns, lines).with_scrollback_limit(0),
+            alt_screen: false,
+            cursor_style: CursorStyle::default(),
+            cursor_visible: true,
+            last_char: None,
+        }
+    }
+
+    /// Limits the scrollback of the primary screen, the alternate one has
+    /// none.
+    pub fn with_scrollback_limit(mut self, limit: usize) -> Self {
+        let primary = if self.alt_screen { &mut self.inactive_grid } else { &mut self.grid };
+        primary.set_scrollback_limit(limit);
+        self
+    }
+
+    /// The screen that is shown right now.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// Whether a full-screen application switched to the alternate screen.
+    pub fn alt_screen(&self) -> bool {
+        self.alt_screen
+    }
+
+    pub fn cursor_style(&self) -> CursorStyle {
+        self.cursor_style
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    /// Called once a frame was drawn, see [`Grid::damage`].
+    pub fn reset_damage(&mut self) {
+        self.grid.reset_damage();
+    }
+
+    pub fn resize(&mut self, columns: usize, lines: usize) {
+        self.grid.resize(columns, lines);
+        self.inactive_grid.resize(columns, lines);
+    }
+
+    /// Processes a chunk of output read from the pty.
+    pub fn advance(&mut self, bytes: &[u8]) {
+        // The parser calls back into the terminal, so it can't stay borrowed.
+        let mut parser = mem::take(&mut self.parser);
+        parser.advance(self, bytes);
+        self.parser = parser;
+    }
+
+    /// Switches between the screens. The alternate screen has no scrollback
+    /// and is cleared on entry, so the primary screen's history stays as it
+    /// was. Both screens share the cursor, unless `save_cursor` keeps the
+    /// primary's aside as for mode 1049.
+    fn set_alt_screen(&mut self, enabled: bool, save_cursor: bool) {
+        if enabled == self.alt_screen {
+            return;
+        }
+        if enabled && save_cursor {
+            self.grid.save_cursor();
+        }
+
+        let cursor = self.grid.cursor;
+        mem::swap(&mut self.grid, &mut self.inactive_grid);
+        self.alt_screen = enabled;
+
+        self.grid.mark_fully_damaged();
+        if enabled {
+            self.grid.cursor = cursor;
+            self.grid.clear_screen(ClearMode::All);
+        } else if save_cursor {
+            self.grid.restore_cursor();
+        } else {
+            self.grid.cursor = cursor;
+        }
+    }
+
+    /// DEC private modes, `CSI ? Pm h` and `CSI ? Pm l`.
+    fn set_private_mode(&mut self, params: &Params, enabled: bool) {
+        for param in params {
+            match param[0] {
+                12 => {
+                    self.cursor_style.blinking = enabled;
+                    self.grid.damage_cursor();
+                }
+                25 => {
+                    self.cursor_visible = enabled;
+                    self.grid.damage_cursor();
+                }
+                47 | 1047 => self.set_alt_screen(enabled, false),
+                69 => self.grid.set_margin_mode(enabled),
+                1049 => self.set_alt_screen(enabled, true),
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Handler for Term {
+    fn print(&mut self, c: char) {
+        let c = self.grid.cursor.charsets.translate(c);
+        self.last_char = Some(c);
+        self.grid.write_char(c);
+    }
+
+    fn execute(&mut self, byte: u8) {
+        let cursor = &mut self.grid.cursor;
+        match byte {
+            b'\r' => self.grid.carriage_return(),
+            b'\n' | 0x0b | 0x0c => self.grid.linefeed(),
+            0x08 => self.grid.backspace(),
+            b'\t' => self.grid.tab_forward(1),
+            // SO and SI.
+            0x0e => cursor.charsets.lock(CharsetIndex::G1),
+            0x0f => cursor.charsets.lock(CharsetIndex::G0),
+            _ => (),
+        }
+    }
+
+    fn esc_dispatch(&mut self, intermediates: &[u8], action: char) {
+        match (intermediates, action) {
+            ([], '7') => self.grid.save_cursor(),
+            ([], '8') => self.grid.restore_cursor(),
+            ([], 'D') => self.grid.linefeed(),
+            ([], 'H') => self.grid.set_tab_stop(),
+            ([], 'E') => {
+                self.grid.carriage_return();
+                self.grid.linefeed();
+            }
+            ([], 'M') => self.grid.reverse_index(),
+            ([], 'N') => self.grid.cursor.charsets.single_shift(CharsetIndex::G2),
+            ([], 'O') => self.grid.cursor.charsets.single_shift(CharsetIndex::G3),
+            ([], 'n') => self.grid.cursor.charsets.lock(CharsetIndex::G2),
+            ([], 'o') => self.grid.cursor.charsets.lock(CharsetIndex::G3),
+            (&[intermediate], designator) => {
+                let index = CharsetIndex::from_intermediate(intermediate);
+                if let (Some(index), Some(charset)) = (index, Charset::from_designator(designator))
+                {
+                    self.grid.cursor.charsets.designate(index, charset);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn csi_dispatch(&mut self, params: &Params, intermediates: &[u8], action: char) {
+        let first = params.iter().next().map_or(0, |param| param[0]);
+        // Counts and positions treat 0 like a missing parameter.
+        let arg_or = |index: usize, default: usize| match params.iter().nth(index) {
+            Some(&[value, ..]) if value > 0 => value as usize,
+            _ => default,
+        };
+        let arg = |index: usize| arg_or(index, 1);
+        let grid = &mut self.grid;
+        match (intermediates, action) {
+            ([], 'A') => grid.move_up(arg(0)),
+            ([], 'B' | 'e') => grid.move_down(arg(0)),
+            ([], 'C' | 'a') => grid.move_forward(arg(0)),
+            ([], 'D') => grid.move_backward(arg(0)),
+            ([], 'E') => {
+                grid.move_down(arg(0));
+                grid.goto_column(0);
+            }
+            ([], 'F') => {
+                grid.move_up(arg(0));
+                grid.goto_column(0);
+            }
+            ([], 'G' | '`') => grid.goto_column(arg(0) - 1),
+            ([], 'H' | 'f') => grid.goto(arg(0) - 1, arg(1) - 1),
+            ([], 'd') => grid.goto_line(arg(0) - 1),
+            ([], 'J') => {
+                let mode = match first {
+                    0 => ClearMode::Below,
+                    1 => ClearMode::Above,
+                    2 => ClearMode::All,
+                    3 => ClearMode::Saved,
+                    _ => return,
+                };
+                grid.clear_screen(mode);
+            }
+            ([], 'K') => {
+                let mode = match first {
+                    0 => LineClearMode::Right,
+                    1 => LineClearMode::Left,
+                    2 => LineClearMode::All,
+                    _ => return,
+                };
+                grid.clear_line(mode);
+            }
+            ([], 'X') => grid.erase_chars(arg(0)),
+            ([], 'I') => grid.tab_forward(arg(0)),
+            ([], 'Z') => grid.tab_backward(arg(0)),
+            ([], 'g') => match first {
+                0 => grid.clear_tab_stop(),
+                3 => grid.clear_all_tab_stops(),
+                _ => (),
+            },
+            ([], '@') => grid.insert_blank(arg(0)),
+            ([], 'P') => grid.delete_chars(arg(0)),
+            ([], 'L') => grid.insert_lines(arg(0)),
+            ([], 'M') => grid.delete_lines(arg(0)),
+            ([], 'S') => grid.scroll_up_lines(arg(0)),
+            ([], 'T') => grid.scroll_down_lines(arg(0)),
+            ([], 'b') => {
+                if let Some(c) = self.last_char {
+                    // Anything beyond a screenful only overwrites itself.
+                    let count = arg(0).min(grid.columns() * grid.lines());
+                    for _ in 0 .. count {
+                        self.print(c);
+                    }
+                }
+            }
+            ([], 'm') => sgr::apply(&mut grid.cursor.template, params),
+            ([], 'r') => grid.set_scroll_region(arg(0) - 1, arg_or(1, grid.lines())),
+            // DECSLRM takes over SCOSC while DECLRMM is on.
+            ([], 's') if grid.margin_mode() => {
+                grid.set_margins(arg(0) - 1, arg_or(1, grid.columns()))
+            }
+            ([], 's') => self.grid.save_cursor(),
+            ([], 'u') => self.grid.restore_cursor(),
+            ([b'?'], 'h') => self.set_private_mode(params, true),
+            ([b'?'], 'l') => self.set_private_mode(params, false),
+            ([b' '], 'q') => {
+                if let Some(style) = CursorStyle::from_decscusr(first) {
+                    self.cursor_style = style;
+                    self.grid.damage_cursor();
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{Read, Write},
+        sync::mpsc,
+        time::Duration,
+    };
+
+    use vtty::{ChildStatus, EventLoop, Loopback, WindowSize};
+
+    use super::*;
+    use crate::grid::{cursor::CursorShape, Damage, LineDamage};
+
+    /// Feeds the output into a terminal and hands it over on exit.
+    struct Emulate {
+        term: Term,
+        tx: mpsc::Sender<(Term, ChildStatus)>,
+    }
+
+    impl vtty::Handler for Emulate {
+        fn on_output(&mut self, bytes: &[u8]) {
+            self.term.advance(bytes);
+        }
+
+        fn on_hangup(&mut self) {}
+
+        fn on_exit(&mut self, status: ChildStatus) {
+            let term = mem::replace(&mut self.term, Term::new(1, 1));
+            let _ = self.tx.send((term, status));
+        }
+    }
+
+    #[test]
+    fn test_loopback_session() {
+        let size = WindowSize { columns: 10, lines: 3, ..Default::default() };
+        let (loopback, mut remote) = Loopback::pair(size).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let handler = Emulate { term: Term::new(10, 3), tx };
+        let (event_loop, notifier) = EventLoop::new(loopback, handler).unwrap();
+        let thread = event_loop.spawn().unwrap();
+
+        notifier.notify("ls\r");
+        let mut input = [0; 3];
+        remote.read_exact(&mut input).unwrap();
+        assert_eq!(&input, b"ls\r");
+
+        remote.write_all(b"$ ls\r\na  b\r\n\x1b[1m$ ").unwrap();
+        remote.exit(ChildStatus::Exited(0));
+        let (term, status) = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        thread.join().unwrap();
+
+        assert_eq!(status, ChildStatus::Exited(0));
+        assert_eq!(term.grid().screen(), "$ ls\na  b\n$");
+        assert_eq!((term.grid().cursor.line, term.grid().cursor.column), (2, 2));
+    }
+
+    #[test]
+    fn test_advance_wraps_and_scrolls() {
+        let mut term = Term::new(3, 2);
+        term.advance(b"abcd\r\nef");
+
+        assert!(term.grid()[0][0].c == Some('d'));
+        assert!(term.grid()[1][0].c == Some('e'));
+        assert!(term.grid()[1][1].c == Some('f'));
+    }
+
+    #[test]
+    fn test_advance_skips_escape_sequences() {
+        let mut term = Term::new(10, 2);
+        term.advance(b"\x1b[1;31ma\x1b]0;title\x07b\x1b[0m");
+
+        assert_eq!(term.grid().screen(), "ab\n");
+    }
+
+    #[test]
+    fn test_pending_wrap() {
+        let mut term = Term::new(3, 2);
+        term.advance(b"abc");
+        assert_eq!((term.grid().cursor.column, term.grid().cursor.input_needs_wrap), (2, true));
+
+        term.advance(b"\rd");
+        assert_eq!(term.grid().screen(), "dbc\n");
+    }
+
+    #[test]
+    fn test_save_and_restore_cursor() {
+        let mut term = Term::new(10, 3);
+        term.advance(b"ab\x1b7\r\nc\x1b8d");
+        assert_eq!(term.grid().screen(), "abd\nc\n");
+
+        term.advance(b"\r\x1b[s\n\x1b[ue");
+        assert_eq!(term.grid().screen(), "ebd\nc\n");
+    }
+
+    #[test]
+    fn test_cursor_movement() {
+        let mut term = Term::new(10, 5);
+        let moves = [
+            ("\x1b[3;4H", (2, 3)),
+            ("\x1b[A", (1, 3)),
+            ("\x1b[2B", (3, 3)),
+            ("\x1b[0C", (3, 4)),
+            ("\x1b[9D", (3, 0)),
+            ("\x1b[2F", (1, 0)),
+            ("\x1b[E", (2, 0)),
+            ("\x1b[7G", (2, 6)),
+            ("\x1b[5d", (4, 6)),
+            ("\x1b[99;99f", (4, 9)),
+            ("\x1b[H", (0, 0)),
+        ];
+
+        for (sequence, expected) in moves {
+            term.advance(sequence.as_bytes());
+            let cursor = term.grid().cursor;
+            assert_eq!((cursor.line, cursor.column), expected, "{sequence:?}");
+        }
+    }
+
+    #[test]
+    fn test_editing() {
+        let mut term = Term::new(6, 3);
+        term.advance(b"abcdef\x1b[2;1Hghijkl\x1b[3;1Hmnopqr");
+
+        term.advance(b"\x1b[1;3H\x1b[K\x1b[2;3H\x1b[2P\x1b[3;2H\x1b[2X");
+        assert_eq!(term.grid().screen(), "ab\nghkl\nm  pqr");
+
+        term.advance(b"\x1b[1;1H\x1b[L\x1b[3;1H\x1b[2J");
+        assert_eq!(term.grid().screen(), "\n\n");
+    }
+
+    #[test]
+    fn test_scroll_region() {
+        let mut term = Term::new(3, 4);
+        term.advance(b"a\r\nb\r\nc\r\nd\x1b[2;3r");
+        assert_eq!(term.grid().scroll_region(), 1 .. 3);
+        assert_eq!(term.grid().cursor.line, 0);
+
+        term.advance(b"\x1b[3H\nx\x1bEy\x1b[2H\x1bMz");
+        assert_eq!(term.grid().screen(), "a\nz\nx\nd");
+    }
+
+    #[test]
+    fn test_left_right_margins() {
+        let mut term = Term::new(4, 2);
+        term.advance(b"\x1b[2;3s");
+        assert_eq!(term.grid().margins(), 0 .. 4);
+
+        term.advance(b"\x1b[?69h\x1b[2;3s\x1b[1;2Habcd");
+        assert_eq!(term.grid().screen(), " ab\n cd");
+    }
+
+    #[test]
+    fn test_damage() {
+        let mut term = Term::new(4, 2);
+        term.advance(b"ab");
+        term.reset_damage();
+        term.advance(b"\x1b[2;1Hc");
+        assert_eq!(
+            term.grid().damage(),
+            Damage::Partial {
+                scrolled: 0,
+                lines: vec![
+                    LineDamage { line: 0, columns: 0 .. 4 },
+                    LineDamage { line: 1, columns: 0 .. 4 },
+                ],
+            }
+        );
+
+        // Hiding the cursor or changing its shape redraws its line.
+        for seq in [&b"\x1b[?25l"[..], b"\x1b[?25h", b"\x1b[5 q", b"\x1b[?12l"] {
+            term.reset_damage();
+            term.advance(seq);
+            assert_eq!(
+                term.grid().damage(),
+                Damage::Partial {
+                    scrolled: 0,
+                    lines: vec![LineDamage { line: 1, columns: 0 .. 4 }],
+                }
+            );
+        }
+
+        term.reset_damage();
+        term.advance(b"\x1b[?1049h");
+        assert_eq!(term.grid().damage(), Damage::Full);
+    }
+
+    #[test]
+    fn test_scrollback_limit() {
+        let mut term = Term::new(4, 2).with_scrollback_limit(3);
+        for _ in 0 .. 100 {
+            term.advance(b"line\r\n");
+        }
+        assert_eq!(term.grid().history_len(), 3);
+    }
+
+    #[test]
+    fn test_alt_screen() {
+        let mut term = Term::new(4, 2);
+        term.advance(b"a\r\nb\r\nc");
+        assert_eq!(term.grid().screen(), "b\nc");
+        assert_eq!(term.grid().history_len(), 1);
+
+        term.advance(b"\x1b[?1049h");
+        assert!(term.alt_screen());
+        assert_eq!(term.grid().screen(), "\n");
+
+        term.advance(b"\x1b[Hvim\r\n~\r\n~\r\n~");
+        assert_eq!(term.grid().history_len(), 0);
+
+        term.advance(b"\x1b[?1049l");
+        assert!(!term.alt_screen());
+        assert_eq!(term.grid().screen(), "b\nc");
+        assert_eq!(term.grid().history_len(), 1);
+        assert_eq!((term.grid().cursor.line, term.grid().cursor.column), (1, 1));
+
+        // Mode 47 shares the cursor between the screens instead.
+        term.advance(b"\x1b[?47hxx");
+        assert_eq!(term.grid().screen(), "\n xx");
+        term.advance(b"\x1b[?47l");
+        assert_eq!((term.grid().cursor.line, term.grid().cursor.column), (1, 3));
+    }
+
+    #[test]
+    fn test_tab_stops() {
+        let mut term = Term::new(20, 1);
+        term.advance(b"a\tb\x1b[2Ic");
+        assert_eq!(term.grid().screen(), "a       b          c");
+
+        term.advance(b"\x1b[3g\x1b[1;5H\x1bH\x1b[1;12H\x1bH\r\tx\ty\x1b[Zz");
+        assert_eq!(term.grid().screen(), "a   x   b  z       c");
+
+        term.advance(b"\x1b[1;5H\x1b[g\r\t-");
+        assert_eq!(term.grid().screen(), "a   x   b  -       c");
+    }
+
+    #[test]
+    fn test_charsets() {
+        let mut term = Term::new(10, 2);
+        term.advance(b"\x1b(0lqk\x1b(B\x1b)0\x0eq\x0fq\r\n\x1b*A\x1bN#\x1b7\x1b(0\x1b8x");
+        assert_eq!(term.grid().screen(), "┌─┐─q\n£x");
+    }
+
+    #[test]
+    fn test_repeat() {
+        let mut term = Term::new(6, 2);
+        term.advance(b"-\x1b[4b");
+        assert_eq!(term.grid().screen(), "-----\n");
+
+        // Capped at a screenful, which fills the rest and scrolls once.
+        term.advance(b"\x1b[99999b");
+        assert_eq!(term.grid().screen(), "------\n-----");
+    }
+
+    #[test]
+    fn test_cursor_visibility_and_shape() {
+        let mut term = Term::new(10, 3);
+        term.advance(b"\x1b[?25l\x1b[5 q");
+        assert!(!term.cursor_visible());
+        assert_eq!(term.cursor_style(), CursorStyle { shape: CursorShape::Beam, blinking: true });
+
+        term.advance(b"\x1b[?25h\x1b[2 q\x1b[?12h");
+        assert!(term.cursor_visible());
+        assert_eq!(term.cursor_style(), CursorStyle { shape: CursorShape::Block, blinking: true });
+    }
+}
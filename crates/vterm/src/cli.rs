@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{path::PathBuf, str::FromStr};
 
 use log::LevelFilter;
 
@@ -8,6 +8,19 @@ pub struct Args {
     pub command: Vec<String>,
     pub log: bool,
     pub log_level: LevelFilter,
+    /// Keep the window open after the child exited.
+    pub hold: bool,
+    /// Variables to set for the child, from `--env=KEY=VALUE`.
+    pub env: Vec<(String, String)>,
+    /// Variables to remove from the child's environment, from `--unset-env=KEY`.
+    pub unset_env: Vec<String>,
+    /// Start the shell as a login shell, ignored for commands.
+    pub login: bool,
+    pub working_directory: Option<PathBuf>,
+    /// Record the session to this asciicast file.
+    pub record: Option<PathBuf>,
+    /// Rows of scrollback to keep, from `--scrollback=LINES`.
+    pub scrollback: Option<usize>,
 }
 
 pub enum WindowProtocol {
@@ -46,12 +59,42 @@ impl Args {
         Args {
             disable_validation: args.contains(&"--disable-validation".to_string()),
             window_protocol,
-            command: args.clone().into_iter().skip(1).collect(),
+            command: Self::parse_command(&args),
             log: args.contains(&"--log".to_string()),
             log_level,
+            hold: args.contains(&"--hold".to_string()),
+            env: Self::parse_values(&args, "--env=")
+                .filter_map(|var| {
+                    let (key, value) = var.split_once('=')?;
+                    Some((key.to_string(), value.to_string()))
+                })
+                .collect(),
+            unset_env: Self::parse_values(&args, "--unset-env=").map(String::from).collect(),
+            login: args.contains(&"--login".to_string()),
+            working_directory: Self::parse_values(&args, "--working-directory=")
+                .last()
+                .map(PathBuf::from),
+            record: Self::parse_values(&args, "--record=").last().map(PathBuf::from),
+            scrollback: Self::parse_values(&args, "--scrollback=")
+                .last()
+                .and_then(|lines| lines.parse().ok()),
         }
     }
 
+    /// The values of a repeatable `--flag=value` option.
+    fn parse_values<'a>(args: &'a [String], prefix: &'a str) -> impl Iterator<Item = &'a str> {
+        args.iter().filter_map(move |arg| arg.strip_prefix(prefix))
+    }
+
+    /// Everything after `-e` or `--` is the command to run instead of the
+    /// user's shell.
+    fn parse_command(args: &[String]) -> Vec<String> {
+        args.iter()
+            .position(|arg| arg == "-e" || arg == "--")
+            .map(|index| args[index + 1 ..].to_vec())
+            .unwrap_or_default()
+    }
+
     pub fn command(&self) -> Option<(&str, &[String])> {
         if self.command.is_empty() {
             return None;
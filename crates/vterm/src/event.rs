@@ -0,0 +1,43 @@
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use vshell::Term;
+use vtty::ChildStatus;
+use winit::event_loop::EventLoopProxy;
+
+/// Events sent to the winit event loop from other threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserEvent {
+    /// The terminal state changed and needs to be drawn again.
+    Wakeup,
+    /// The child process was reaped.
+    ChildExit(ChildStatus),
+}
+
+/// Feeds the output of the child into the terminal state and wakes up the
+/// window afterwards.
+pub struct PtyHandler {
+    term: Arc<Mutex<Term>>,
+    proxy: EventLoopProxy<UserEvent>,
+}
+
+impl PtyHandler {
+    pub fn new(term: Arc<Mutex<Term>>, proxy: EventLoopProxy<UserEvent>) -> Self {
+        Self { term, proxy }
+    }
+}
+
+impl vtty::Handler for PtyHandler {
+    fn on_output(&mut self, bytes: &[u8]) {
+        self.term.lock().advance(bytes);
+        let _ = self.proxy.send_event(UserEvent::Wakeup);
+    }
+
+    fn on_hangup(&mut self) {
+        let _ = self.proxy.send_event(UserEvent::Wakeup);
+    }
+
+    fn on_exit(&mut self, status: ChildStatus) {
+        let _ = self.proxy.send_event(UserEvent::ChildExit(status));
+    }
+}
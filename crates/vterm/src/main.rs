@@ -1,9 +1,22 @@
-use std::{borrow::BorrowMut, sync::Arc, time::Instant};
+use std::{
+    borrow::BorrowMut,
+    fs,
+    path::Path,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{Context, Ok, Result};
 use ash::Entry;
-use log::info;
+use log::{info, warn};
 use logger::{initialize_logger, initialize_panic_hook};
+use parking_lot::Mutex;
+use vshell::{input::InputState, Term};
+use vtty::{Environment, EventLoop as PtyEventLoop, Foreground, Notifier, Pty, WindowSize};
 use vui::{
     asset_loader::AssetLoader,
     errors::FrameError,
@@ -23,23 +36,30 @@ use windows_sys::Win32::System::Console::{AttachConsole, FreeConsole, ATTACH_PAR
 use winit::{
     application::ApplicationHandler,
     dpi::PhysicalSize,
-    event::{DeviceEvent, DeviceId, StartCause, WindowEvent},
+    event::{DeviceEvent, DeviceId, ElementState, StartCause, WindowEvent},
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
     platform::{wayland::EventLoopBuilderExtWayland, x11::EventLoopBuilderExtX11},
     window::{Window, WindowId},
 };
 
 use crate::{
     cli::{Args, WindowProtocol},
+    event::{PtyHandler, UserEvent},
     terminal::Terminal,
 };
 
 mod cli;
+mod event;
 mod lifecycle;
 mod logger;
 mod terminal;
 
 const WINDOW_TITLE: &str = "vterm";
+const DEFAULT_COLUMNS: usize = 80;
+const DEFAULT_LINES: usize = 24;
+/// How often the foreground process is looked up for the window title.
+const TITLE_UPDATE_INTERVAL: Duration = Duration::from_millis(250);
 const VULKAN_APP_NAME: &str = "vterm";
 const VULKAN_APP_VERSION: (u32, u32, u32) = (0, 0, 0);
 const VULKAN_ENGINE_NAME: &str = "viableui";
@@ -62,9 +82,18 @@ struct AppState {
     vk_alloc: Option<Arc<dyn MemoryAllocator>>,
     camera: Mat4,
     root: Option<UI<Terminal>>,
+    term: Arc<Mutex<Term>>,
+    notifier: Notifier,
+    pty_thread: Option<JoinHandle<()>>,
+    input: InputState,
+    modifiers: ModifiersState,
+    foreground: Foreground,
+    title: String,
+    last_title_update: Instant,
+    recording: bool,
 }
 
-impl ApplicationHandler for AppState {
+impl ApplicationHandler<UserEvent> for AppState {
     fn new_events(&mut self, event_loop: &ActiveEventLoop, cause: StartCause) {
         if cause == StartCause::Init {
             event_loop.set_control_flow(ControlFlow::Poll);
@@ -124,6 +153,8 @@ impl ApplicationHandler for AppState {
         self.asset_loader = Some(asset_loader);
         self.frame_layer = Some(frame_layer);
         self.root = Some(root);
+
+        self.resize_terminal(self.last_window_size.unwrap());
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
@@ -136,6 +167,20 @@ impl ApplicationHandler for AppState {
                 if Some(new_size) != self.last_window_size {
                     self.last_window_size = Some(new_size);
                     self.swapchain_needs_rebuild = true;
+                    self.resize_terminal(new_size);
+                }
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+            }
+            WindowEvent::KeyboardInput { event, .. } if event.state == ElementState::Pressed => {
+                let toggle_recording = event.physical_key == PhysicalKey::Code(KeyCode::KeyR) &&
+                    self.modifiers.control_key() &&
+                    self.modifiers.shift_key();
+                if toggle_recording {
+                    self.toggle_recording();
+                } else {
+                    self.input.apply_keyboard(event, &self.modifiers);
                 }
             }
             WindowEvent::CloseRequested => {
@@ -145,6 +190,30 @@ impl ApplicationHandler for AppState {
         }
     }
 
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, event: UserEvent) {
+        match event {
+            UserEvent::Wakeup => {
+                if let Some(window) = self.window.as_ref() {
+                    window.request_redraw();
+                }
+            }
+            UserEvent::ChildExit(status) => {
+                info!("[user_event] child {status}");
+                if !self.args.hold {
+                    event_loop.exit();
+                    return;
+                }
+
+                // Keep the last screen around and say what happened.
+                let banner = format!("\r\n[process {status}]");
+                self.term.lock().advance(banner.as_bytes());
+                if let Some(window) = self.window.as_ref() {
+                    window.request_redraw();
+                }
+            }
+        }
+    }
+
     fn device_event(&mut self, _: &ActiveEventLoop, _: DeviceId, _event: DeviceEvent) {
         // TODO: Handle input events
     }
@@ -154,6 +223,8 @@ impl ApplicationHandler for AppState {
         let _delta_time = (current_frame_timestamp - self.last_frame_timestamp).as_secs_f32();
         self.last_frame_timestamp = current_frame_timestamp;
 
+        self.update_title();
+
         if self.swapchain_needs_rebuild {
             self.rebuild_swapchain_resources().unwrap();
             self.swapchain_needs_rebuild = false;
@@ -176,10 +247,81 @@ impl ApplicationHandler for AppState {
 
     fn exiting(&mut self, _: &ActiveEventLoop) {
         // Cleanup resources
+        self.notifier.shutdown();
+        if let Some(pty_thread) = self.pty_thread.take() {
+            let _ = pty_thread.join();
+        }
     }
 }
 
 impl AppState {
+    /// Starts recording to a new file in `~/.vterm/recordings`, or stops the
+    /// running recording.
+    fn toggle_recording(&mut self) {
+        if self.recording {
+            info!("[toggle_recording] stopped recording");
+            self.notifier.stop_recording();
+            self.recording = false;
+            return;
+        }
+
+        let Some(dir) = dirs::home_dir().map(|home| home.join(".vterm/recordings")) else {
+            warn!("[toggle_recording] no home directory to record to");
+            return;
+        };
+        if let Err(err) = fs::create_dir_all(&dir) {
+            warn!("[toggle_recording] unable to create {}: {err}", dir.display());
+            return;
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = dir.join(format!("{timestamp}.cast"));
+        info!("[toggle_recording] recording to {}", path.display());
+        self.notifier.start_recording(path);
+        self.recording = true;
+    }
+
+    /// Names the window after the foreground process and its directory, e.g.
+    /// `vim ~/src/foo`.
+    fn update_title(&mut self) {
+        if self.last_title_update.elapsed() < TITLE_UPDATE_INTERVAL {
+            return;
+        }
+        self.last_title_update = Instant::now();
+
+        let Some(process) = self.foreground.process() else {
+            return;
+        };
+        let title = match process.cwd {
+            Some(cwd) => format!("{} {}", process.name, shorten_home(&cwd)),
+            None => process.name,
+        };
+        if title != self.title {
+            if let Some(window) = self.window.as_ref() {
+                window.set_title(&title);
+            }
+            self.title = title;
+        }
+    }
+
+    /// Fits the grid to the window and lets the child know about it.
+    fn resize_terminal(&mut self, size: PhysicalSize<u32>) {
+        let (cell_width, cell_height) = self.root.as_ref().unwrap().state().cell_size();
+        let columns = ((size.width as f32 / cell_width) as u16).max(1);
+        let lines = ((size.height as f32 / cell_height) as u16).max(1);
+
+        self.term.lock().resize(columns as usize, lines as usize);
+
+        let window_size = WindowSize {
+            columns,
+            lines,
+            cell_width: cell_width as u16,
+            cell_height: cell_height as u16,
+        };
+        self.notifier.resize(window_size);
+        info!("[resize_terminal] {columns}x{lines}");
+    }
+
     fn compose_frame(&mut self) -> Result<(), FrameError> {
         let (index, cmds) = self.frame_pipeline.as_mut().unwrap().begin_frame()?;
 
@@ -232,23 +374,40 @@ impl AppState {
     }
 }
 
-pub fn setup_environment_variables() {
-    unsafe {
-        #[cfg(unix)]
-        {
-            let terminfo = "xterm-256color";
-            info!("[setup_environment_variables] terminfo: {terminfo}");
-            std::env::set_var("TERM", terminfo);
-        }
+/// Builds the environment of a pane's child from vterm's own, the defaults
+/// every terminal program expects and the overrides given on the command line.
+fn child_environment(args: &Args, pane_id: u32) -> Environment {
+    let mut environment = Environment::inherit();
+
+    #[cfg(unix)]
+    {
+        let terminfo = "xterm-256color";
+        info!("[child_environment] terminfo: {terminfo}");
+        environment = environment.set("TERM", terminfo);
+    }
 
-        std::env::set_var("TERM_PROGRAM", "vterm");
-        std::env::set_var("TERM_PROGRAM_VERSION", env!("CARGO_PKG_VERSION"));
+    environment = environment
+        .set("TERM_PROGRAM", "vterm")
+        .set("TERM_PROGRAM_VERSION", env!("CARGO_PKG_VERSION"))
+        .set("COLORTERM", "truecolor")
+        .set("VTERM_PANE_ID", pane_id.to_string())
+        .remove("DESKTOP_STARTUP_ID")
+        .remove("XDG_ACTIVATION_TOKEN");
 
-        std::env::set_var("COLORTERM", "truecolor");
-        std::env::remove_var("DESKTOP_STARTUP_ID");
-        std::env::remove_var("XDG_ACTIVATION_TOKEN");
+    for (key, value) in &args.env {
+        environment = environment.set(key, value);
+    }
+    for key in &args.unset_env {
+        environment = environment.remove(key);
     }
-    // TODO(nuii): add env vars from config...
+
+    environment
+}
+
+/// Hands out a unique id for every pane.
+fn next_pane_id() -> u32 {
+    static NEXT_PANE_ID: AtomicU32 = AtomicU32::new(0);
+    NEXT_PANE_ID.fetch_add(1, Ordering::Relaxed)
 }
 
 pub fn main() -> Result<()> {
@@ -269,9 +428,22 @@ pub fn main() -> Result<()> {
             // TODO(nuii): flatpak.
         }
     }
-    setup_environment_variables();
 
     let event_loop = create_event_loop(&args);
+    let mut term = Term::new(DEFAULT_COLUMNS, DEFAULT_LINES);
+    if let Some(limit) = args.scrollback {
+        term = term.with_scrollback_limit(limit);
+    }
+    let term = Arc::new(Mutex::new(term));
+    let pty = spawn_pty(&args)?;
+    let foreground = pty.foreground()?;
+    let handler = PtyHandler::new(term.clone(), event_loop.create_proxy());
+    let (pty_event_loop, notifier) = PtyEventLoop::new(pty, handler)?;
+    if let Some(path) = &args.record {
+        notifier.start_recording(path);
+    }
+    let pty_thread = pty_event_loop.spawn()?;
+    let recording = args.record.is_some();
     let mut app_state = AppState {
         window: None,
         last_window_size: None,
@@ -288,6 +460,15 @@ pub fn main() -> Result<()> {
         vk_alloc: None,
         camera: Mat4::identity(),
         root: None,
+        term,
+        input: InputState::new(notifier.clone()),
+        notifier,
+        pty_thread: Some(pty_thread),
+        modifiers: ModifiersState::empty(),
+        foreground,
+        title: WINDOW_TITLE.into(),
+        last_title_update: Instant::now(),
+        recording,
     };
     event_loop.run_app(&mut app_state).unwrap();
 
@@ -299,8 +480,37 @@ pub fn main() -> Result<()> {
     Ok(())
 }
 
-fn create_event_loop(args: &Args) -> EventLoop<()> {
-    let mut event_loop = EventLoop::builder();
+/// Replaces the home directory prefix with `~`.
+fn shorten_home(path: &Path) -> String {
+    match dirs::home_dir().and_then(|home| path.strip_prefix(home).ok().map(Path::to_path_buf)) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".into(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+fn spawn_pty(args: &Args) -> Result<Pty> {
+    let mut builder = Pty::builder()
+        .window_size(WindowSize {
+            columns: DEFAULT_COLUMNS as u16,
+            lines: DEFAULT_LINES as u16,
+            ..Default::default()
+        })
+        .environment(child_environment(args, next_pane_id()));
+    match args.command() {
+        Some((program, program_args)) => builder = builder.command(program, program_args),
+        None => builder = builder.login(args.login),
+    }
+    if let Some(working_directory) = &args.working_directory {
+        builder = builder.working_directory(working_directory);
+    }
+    let pty = builder.spawn()?;
+    info!("[spawn_pty] child running with pid {}", pty.child_id());
+    Ok(pty)
+}
+
+fn create_event_loop(args: &Args) -> EventLoop<UserEvent> {
+    let mut event_loop = EventLoop::with_user_event();
     _ = match args.window_protocol {
         Some(WindowProtocol::Wayland) => event_loop.with_wayland(),
         Some(WindowProtocol::X11) => event_loop.with_x11(),
@@ -2,7 +2,10 @@ use ::vui::{
     asset_loader::AssetLoader,
     ui::{font::FontFamily, widgets::prelude::*, UIState},
 };
-use vui::ui::{color::Color, font::FontConfig};
+use vui::ui::{
+    color::Color,
+    font::{Font, FontConfig},
+};
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum TerminalMessage {
@@ -11,6 +14,7 @@ pub enum TerminalMessage {
 
 pub struct Terminal {
     em: f32,
+    font: Font,
     label_1: Element<TerminalMessage>,
     label_2: Element<TerminalMessage>,
     image: Element<TerminalMessage>,
@@ -22,6 +26,7 @@ impl Terminal {
         let em = 16.0 * content_scale;
         let font_32 = FontFamily::new(FontConfig::default(), 2.0 * em, asset_loader)?;
         let font_64 = FontFamily::new(FontConfig::default(), 4.0 * em, asset_loader)?;
+        let font = FontFamily::new(FontConfig::default(), em, asset_loader)?.regular;
 
         let image_dir = dirs::home_dir().unwrap().join(".vterm/assets/images/");
         let img_rust = asset_loader.read_texture(image_dir.join("rust.png"))?;
@@ -51,7 +56,12 @@ impl Terminal {
             .gradient(Color::new(1.0, 1.0, 1.0, 1.0), Color::new(1.0, 1.0, 1.0, 0.3))
             .into();
 
-        Ok(Self { em, label_1, label_2, image, label_3 })
+        Ok(Self { em, font, label_1, label_2, image, label_3 })
+    }
+
+    /// The size of a single grid cell in pixels, as (width, height).
+    pub fn cell_size(&self) -> (f32, f32) {
+        (self.font.h_advance('M').ceil(), self.font.line_height().ceil())
     }
 }
 
@@ -0,0 +1,277 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Lines, Write},
+    path::Path,
+    str,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use serde_json::{json, Value};
+
+use crate::WindowSize;
+
+/// Records a pty session in asciinema's asciicast v2 format.
+///
+/// The file starts with a JSON header line, followed by one JSON array per
+/// event: `[seconds, "o" | "i" | "r", data]`. Both directions are recorded
+/// exactly as they passed through the pty.
+#[derive(Debug)]
+pub struct Recorder<W: Write> {
+    writer: W,
+    start: Instant,
+    /// Incomplete UTF-8 sequences left over from the last read, per direction.
+    pending_output: Vec<u8>,
+    pending_input: Vec<u8>,
+}
+
+impl Recorder<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P, window_size: WindowSize) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), window_size)
+    }
+}
+
+impl<W: Write> Recorder<W> {
+    /// Writes the header right away, events are timed relative to this call.
+    pub fn new(mut writer: W, window_size: WindowSize) -> io::Result<Self> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let header = json!({
+            "version": 2,
+            "width": window_size.columns,
+            "height": window_size.lines,
+            "timestamp": timestamp,
+        });
+        writeln!(writer, "{header}")?;
+
+        Ok(Self { writer, start: Instant::now(), pending_output: vec![], pending_input: vec![] })
+    }
+
+    /// Records bytes read from the child.
+    pub fn output(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let data = decode(&mut self.pending_output, bytes);
+        self.event("o", &data)
+    }
+
+    /// Records bytes written to the child.
+    pub fn input(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let data = decode(&mut self.pending_input, bytes);
+        self.event("i", &data)
+    }
+
+    pub fn resize(&mut self, window_size: WindowSize) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", window_size.columns, window_size.lines))
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        // Microseconds are what asciinema itself records.
+        let time = (self.start.elapsed().as_secs_f64() * 1e6).round() / 1e6;
+        writeln!(self.writer, "{}", json!([time, code, data]))
+    }
+}
+
+/// The header line of a recording.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub width: u16,
+    pub height: u16,
+}
+
+/// A single event of a recording.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    /// Time since the start of the recording.
+    pub time: Duration,
+    pub kind: EventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    Output(String),
+    Input(String),
+    Resize {
+        columns: u16,
+        lines: u16,
+    },
+    /// Markers and event types added by later versions of the format.
+    Other(String, String),
+}
+
+/// Reads recordings written by [`Recorder`], or by asciinema itself.
+#[derive(Debug)]
+pub struct Reader<R> {
+    header: Header,
+    lines: Lines<R>,
+}
+
+impl Reader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Reads the header, events are read lazily by iterating.
+    pub fn new(reader: R) -> io::Result<Self> {
+        let mut lines = reader.lines();
+        let line = lines.next().ok_or_else(|| invalid("empty recording"))??;
+        let header: Value = serde_json::from_str(&line)?;
+        if header["version"] != 2 {
+            return Err(invalid("only asciicast v2 is supported"));
+        }
+        let dimension = |key| {
+            header[key]
+                .as_u64()
+                .and_then(|value| u16::try_from(value).ok())
+                .ok_or_else(|| invalid(&format!("missing {key} in header")))
+        };
+        let header = Header { width: dimension("width")?, height: dimension("height")? };
+
+        Ok(Self { header, lines })
+    }
+
+    pub fn header(&self) -> Header {
+        self.header
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = loop {
+            match self.lines.next()? {
+                Ok(line) if line.trim().is_empty() => continue,
+                line => break line,
+            }
+        };
+        Some(line.and_then(|line| parse_event(&line)))
+    }
+}
+
+fn parse_event(line: &str) -> io::Result<Event> {
+    let event: (f64, String, String) = serde_json::from_str(line)?;
+    let (time, code, data) = event;
+    let time = Duration::try_from_secs_f64(time).map_err(|_| invalid("invalid event time"))?;
+    let kind = match code.as_str() {
+        "o" => EventKind::Output(data),
+        "i" => EventKind::Input(data),
+        "r" => {
+            let (columns, lines) = data
+                .split_once('x')
+                .and_then(|(columns, lines)| Some((columns.parse().ok()?, lines.parse().ok()?)))
+                .ok_or_else(|| invalid("invalid resize event"))?;
+            EventKind::Resize { columns, lines }
+        }
+        _ => EventKind::Other(code, data),
+    };
+
+    Ok(Event { time, kind })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Decodes as much of `pending` and `bytes` as possible. A sequence cut off at
+/// the end is kept for the next call, since reads split characters anywhere.
+fn decode(pending: &mut Vec<u8>, bytes: &[u8]) -> String {
+    pending.extend_from_slice(bytes);
+
+    let mut text = String::with_capacity(pending.len());
+    let mut rest = &pending[..];
+    loop {
+        match str::from_utf8(rest) {
+            Ok(valid) => {
+                text.push_str(valid);
+                rest = &[];
+                break;
+            }
+            Err(err) => {
+                let (valid, invalid) = rest.split_at(err.valid_up_to());
+                text.push_str(str::from_utf8(valid).unwrap_or_default());
+                match err.error_len() {
+                    Some(len) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        rest = &invalid[len ..];
+                    }
+                    None => {
+                        rest = invalid;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    let consumed = pending.len() - rest.len();
+    pending.drain(.. consumed);
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_record() {
+        let size = WindowSize { columns: 80, lines: 24, ..Default::default() };
+        let mut recorder = Recorder::new(vec![], size).unwrap();
+        recorder.output(b"caf\xc3").unwrap();
+        recorder.output(b"\xa9\r\n").unwrap();
+        recorder.input(b"q").unwrap();
+
+        let recording = String::from_utf8(recorder.writer).unwrap();
+        let lines: Vec<Value> =
+            recording.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 80);
+        assert_eq!(lines[0]["height"], 24);
+        assert_eq!(lines[1][1], "o");
+        assert_eq!(lines[1][2], "caf");
+        assert_eq!(lines[2][2], "é\r\n");
+        assert_eq!(lines[3][1], "i");
+        assert_eq!(lines[3][2], "q");
+    }
+
+    #[test]
+    fn test_read() {
+        let size = WindowSize { columns: 80, lines: 24, ..Default::default() };
+        let mut recorder = Recorder::new(vec![], size).unwrap();
+        recorder.output(b"hello").unwrap();
+        recorder.resize(WindowSize { columns: 100, lines: 30, ..size }).unwrap();
+        recorder.input(b"q").unwrap();
+
+        let reader = Reader::new(&recorder.writer[..]).unwrap();
+        assert_eq!(reader.header(), Header { width: 80, height: 24 });
+
+        let kinds: Vec<EventKind> = reader.map(|event| event.unwrap().kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::Output("hello".into()),
+                EventKind::Resize { columns: 100, lines: 30 },
+                EventKind::Input("q".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        assert!(Reader::new(&b""[..]).is_err());
+        assert!(Reader::new(&b"{\"version\": 1, \"width\": 80, \"height\": 24}"[..]).is_err());
+
+        let mut reader =
+            Reader::new(&b"{\"version\": 2, \"width\": 80, \"height\": 24}\n[0.5, \"o\"]"[..])
+                .unwrap();
+        assert!(reader.next().unwrap().is_err());
+    }
+}
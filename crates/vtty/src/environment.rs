@@ -0,0 +1,52 @@
+use std::{
+    collections::{btree_map, BTreeMap},
+    env,
+    ffi::{OsStr, OsString},
+};
+
+/// The complete environment of a child process.
+///
+/// It is applied to the child only, so vterm's own environment never has to
+/// be touched to configure a shell.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    vars: BTreeMap<OsString, OsString>,
+}
+
+impl Environment {
+    /// Starts out with a copy of vterm's own environment.
+    pub fn inherit() -> Self {
+        Self { vars: env::vars_os().collect() }
+    }
+
+    pub fn set<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: Into<OsString>,
+        V: Into<OsString>,
+    {
+        self.vars.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn remove<K: AsRef<OsStr>>(mut self, key: K) -> Self {
+        self.vars.remove(key.as_ref());
+        self
+    }
+
+    pub fn get<K: AsRef<OsStr>>(&self, key: K) -> Option<&OsStr> {
+        self.vars.get(key.as_ref()).map(OsString::as_os_str)
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<'_, OsString, OsString> {
+        self.vars.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Environment {
+    type Item = (&'a OsString, &'a OsString);
+    type IntoIter = btree_map::Iter<'a, OsString, OsString>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -0,0 +1,36 @@
+use std::io;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PtyError {
+    #[error("Unable to open a pseudoterminal pair")]
+    UnableToOpenPty(#[source] io::Error),
+
+    #[error("Unable to configure the pseudoterminal")]
+    UnableToConfigurePty(#[source] io::Error),
+
+    #[error("Unable to resize the pseudoterminal")]
+    UnableToResize(#[source] io::Error),
+
+    #[error("Unable to poll the pseudoterminal")]
+    UnableToPoll(#[source] io::Error),
+
+    #[error("Unable to read from the pseudoterminal")]
+    UnableToRead(#[source] io::Error),
+
+    #[error("Unable to write to the pseudoterminal")]
+    UnableToWrite(#[source] io::Error),
+
+    #[error("Unable to wait for the child process")]
+    UnableToWait(#[source] io::Error),
+
+    #[error("Unable to install the SIGCHLD handler")]
+    UnableToHandleSignals(#[source] io::Error),
+
+    #[error("Unable to spawn the pseudoterminal I/O thread")]
+    UnableToSpawnThread(#[source] io::Error),
+
+    #[error("Unable to spawn the child process `{0}`")]
+    UnableToSpawn(String, #[source] io::Error),
+}
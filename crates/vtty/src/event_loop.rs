@@ -0,0 +1,491 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufWriter, ErrorKind, Read},
+    os::{fd::AsRawFd, unix::net::UnixStream},
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use log::{debug, error, warn};
+use mio::{unix::SourceFd, Events, Interest, Poll, Token, Waker};
+use signal_hook::{consts::SIGCHLD, low_level::pipe, SigId};
+
+use crate::{asciicast::Recorder, ChildStatus, PtyError, Tty, WindowSize};
+
+const PTY_TOKEN: Token = Token(0);
+const WAKER_TOKEN: Token = Token(1);
+const CHILD_TOKEN: Token = Token(2);
+
+/// How much is read from the pty in one go.
+const READ_BUFFER_SIZE: usize = 0x10000;
+
+/// Receives everything the I/O thread has to report.
+pub trait Handler: Send + 'static {
+    /// Called with every chunk of output read from the child.
+    fn on_output(&mut self, bytes: &[u8]);
+
+    /// Called once the child closed its side of the pty. Its exit status
+    /// usually follows shortly after.
+    fn on_hangup(&mut self);
+
+    /// Called once the child was reaped. The I/O thread stops right after.
+    fn on_exit(&mut self, status: ChildStatus);
+}
+
+/// Requests for the I/O thread.
+#[derive(Debug)]
+pub enum Msg {
+    /// Bytes to write to the child.
+    Input(Vec<u8>),
+    Resize(WindowSize),
+    /// Records the session to an asciicast file from now on.
+    StartRecording(PathBuf),
+    StopRecording,
+    Shutdown,
+}
+
+/// The sending half of the I/O thread's queue.
+///
+/// Every message wakes up the thread, so writes reach the child right away.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    tx: Sender<Msg>,
+    waker: Arc<Waker>,
+}
+
+impl Notifier {
+    /// Queues `bytes` to be written to the child.
+    pub fn notify<B: Into<Vec<u8>>>(&self, bytes: B) {
+        let bytes = bytes.into();
+        if !bytes.is_empty() {
+            self.send(Msg::Input(bytes));
+        }
+    }
+
+    pub fn resize(&self, window_size: WindowSize) {
+        self.send(Msg::Resize(window_size));
+    }
+
+    pub fn start_recording<P: Into<PathBuf>>(&self, path: P) {
+        self.send(Msg::StartRecording(path.into()));
+    }
+
+    pub fn stop_recording(&self) {
+        self.send(Msg::StopRecording);
+    }
+
+    pub fn shutdown(&self) {
+        self.send(Msg::Shutdown);
+    }
+
+    fn send(&self, msg: Msg) {
+        if self.tx.send(msg).is_err() {
+            // The thread is already gone, nobody is left to write to.
+            return;
+        }
+        if let Err(err) = self.waker.wake() {
+            warn!("[Notifier::send] unable to wake the pty thread: {err}");
+        }
+    }
+}
+
+/// Shuffles bytes between a [`Tty`] and vterm on a dedicated thread.
+///
+/// Output is read without blocking and handed to the [`Handler`], while input
+/// from the [`Notifier`] is queued and written whenever the tty accepts it.
+pub struct EventLoop<T: Tty, H: Handler> {
+    poll: Poll,
+    pty: T,
+    rx: Receiver<Msg>,
+    handler: H,
+    write_queue: VecDeque<Vec<u8>>,
+    /// How much of the front of `write_queue` was already written.
+    written: usize,
+    interest: Interest,
+    hung_up: bool,
+    /// Read end of the self-pipe the SIGCHLD handler writes to.
+    signals: UnixStream,
+    signal_id: SigId,
+    recorder: Option<Recorder<BufWriter<File>>>,
+}
+
+/// Whether the event loop should keep going.
+enum Flow {
+    Continue,
+    Stop,
+}
+
+impl<T: Tty, H: Handler> EventLoop<T, H> {
+    pub fn new(pty: T, handler: H) -> Result<(Self, Notifier), PtyError> {
+        pty.set_nonblocking()?;
+
+        let poll = Poll::new().map_err(PtyError::UnableToPoll)?;
+        let waker =
+            Arc::new(Waker::new(poll.registry(), WAKER_TOKEN).map_err(PtyError::UnableToPoll)?);
+        poll.registry()
+            .register(&mut SourceFd(&pty.as_raw_fd()), PTY_TOKEN, Interest::READABLE)
+            .map_err(PtyError::UnableToPoll)?;
+
+        let (signals, signal_tx) = UnixStream::pair().map_err(PtyError::UnableToHandleSignals)?;
+        signals.set_nonblocking(true).map_err(PtyError::UnableToHandleSignals)?;
+        let signal_id =
+            pipe::register(SIGCHLD, signal_tx).map_err(PtyError::UnableToHandleSignals)?;
+        poll.registry()
+            .register(&mut SourceFd(&signals.as_raw_fd()), CHILD_TOKEN, Interest::READABLE)
+            .map_err(PtyError::UnableToPoll)?;
+
+        let (tx, rx) = mpsc::channel();
+        let event_loop = Self {
+            poll,
+            pty,
+            rx,
+            handler,
+            write_queue: VecDeque::new(),
+            written: 0,
+            interest: Interest::READABLE,
+            hung_up: false,
+            signals,
+            signal_id,
+            recorder: None,
+        };
+
+        Ok((event_loop, Notifier { tx, waker }))
+    }
+
+    /// Moves the loop onto its own thread. The thread ends when the child
+    /// exits or on [`Notifier::shutdown`], dropping the pty with it.
+    pub fn spawn(mut self) -> Result<JoinHandle<()>, PtyError> {
+        thread::Builder::new()
+            .name("vtty".into())
+            .spawn(move || {
+                if let Err(err) = self.run() {
+                    error!("[EventLoop::run] {err}");
+                }
+            })
+            .map_err(PtyError::UnableToSpawnThread)
+    }
+
+    fn run(&mut self) -> Result<(), PtyError> {
+        let mut events = Events::with_capacity(64);
+        let mut buf = vec![0; READ_BUFFER_SIZE];
+
+        // The child might have exited before the SIGCHLD handler was in place.
+        if let Flow::Stop = self.child_event(&mut buf)? {
+            return Ok(());
+        }
+
+        loop {
+            if let Err(err) = self.poll.poll(&mut events, None) {
+                if err.kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(PtyError::UnableToPoll(err));
+            }
+
+            for event in events.iter() {
+                let flow = match event.token() {
+                    WAKER_TOKEN => self.drain_channel()?,
+                    CHILD_TOKEN => self.child_event(&mut buf)?,
+                    PTY_TOKEN if event.is_readable() || event.is_read_closed() => {
+                        self.pty_read(&mut buf)?
+                    }
+                    _ => Flow::Continue,
+                };
+                if let Flow::Stop = flow {
+                    return Ok(());
+                }
+
+                if event.token() == PTY_TOKEN && event.is_writable() {
+                    if let Flow::Stop = self.pty_write()? {
+                        return Ok(());
+                    }
+                }
+            }
+
+            self.update_interest()?;
+            self.record(|recorder| recorder.flush());
+        }
+    }
+
+    /// Reaps the child if SIGCHLD was about it, after picking up whatever
+    /// output it left behind.
+    fn child_event(&mut self, buf: &mut [u8]) -> Result<Flow, PtyError> {
+        let mut drain = [0; 64];
+        while let Ok(n) = (&self.signals).read(&mut drain) {
+            if n == 0 {
+                break;
+            }
+        }
+
+        let Some(status) = self.pty.try_wait()? else {
+            return Ok(Flow::Continue);
+        };
+        debug!("[EventLoop::child_event] child {status}");
+
+        if !self.hung_up {
+            if let Flow::Stop = self.pty_read(buf)? {
+                return Ok(Flow::Stop);
+            }
+        }
+        self.handler.on_exit(status);
+        Ok(Flow::Stop)
+    }
+
+    fn drain_channel(&mut self) -> Result<Flow, PtyError> {
+        loop {
+            match self.rx.try_recv() {
+                // Nobody reads input anymore after a hangup.
+                Ok(Msg::Input(_)) if self.hung_up => (),
+                Ok(Msg::Input(bytes)) => {
+                    self.record(|recorder| recorder.input(&bytes));
+                    self.write_queue.push_back(bytes);
+                }
+                // A failed resize leaves the child at the old size, which is
+                // no reason to stop serving it.
+                Ok(Msg::Resize(window_size)) => match self.pty.resize(window_size) {
+                    Ok(()) => self.record(|recorder| recorder.resize(window_size)),
+                    Err(err) => warn!("[EventLoop::drain_channel] {err}"),
+                },
+                Ok(Msg::StartRecording(path)) => self.start_recording(path)?,
+                Ok(Msg::StopRecording) => {
+                    self.record(|recorder| recorder.flush());
+                    self.recorder = None;
+                }
+                Ok(Msg::Shutdown) | Err(TryRecvError::Disconnected) => {
+                    debug!("[EventLoop::drain_channel] shutting down");
+                    return Ok(Flow::Stop);
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        // Try right away, most writes fit into the pty buffer.
+        self.pty_write()
+    }
+
+    /// Reads until the pty would block. Registration is edge triggered, so
+    /// stopping any earlier would lose the wakeup for the remaining data.
+    fn pty_read(&mut self, buf: &mut [u8]) -> Result<Flow, PtyError> {
+        loop {
+            match self.pty.read(buf) {
+                Ok(0) => return self.hangup(),
+                Ok(n) => {
+                    self.record(|recorder| recorder.output(&buf[.. n]));
+                    self.handler.on_output(&buf[.. n]);
+                }
+                Err(err) => match err.kind() {
+                    ErrorKind::WouldBlock => return Ok(Flow::Continue),
+                    ErrorKind::Interrupted => continue,
+                    // Linux reports EIO once the last slave fd is closed.
+                    _ if err.raw_os_error() == Some(libc::EIO) => return self.hangup(),
+                    _ => return Err(PtyError::UnableToRead(err)),
+                },
+            }
+        }
+    }
+
+    /// Writes as much of the queue as the pty takes. A child that went away
+    /// is a hangup, not an error, so its exit status still gets reported.
+    fn pty_write(&mut self) -> Result<Flow, PtyError> {
+        if self.hung_up {
+            self.write_queue.clear();
+            self.written = 0;
+            return Ok(Flow::Continue);
+        }
+        while let Some(bytes) = self.write_queue.front() {
+            match self.pty.write(&bytes[self.written ..]) {
+                Ok(n) => {
+                    self.written += n;
+                    if self.written == bytes.len() {
+                        self.write_queue.pop_front();
+                        self.written = 0;
+                    }
+                }
+                Err(err) => match err.kind() {
+                    ErrorKind::WouldBlock => break,
+                    ErrorKind::Interrupted => continue,
+                    _ if matches!(err.raw_os_error(), Some(libc::EIO | libc::EPIPE)) => {
+                        self.write_queue.clear();
+                        self.written = 0;
+                        return self.hangup();
+                    }
+                    _ => return Err(PtyError::UnableToWrite(err)),
+                },
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn start_recording(&mut self, path: PathBuf) -> Result<(), PtyError> {
+        let window_size = self.pty.window_size()?;
+        match Recorder::create(&path, window_size) {
+            Ok(recorder) => {
+                debug!("[EventLoop::start_recording] recording to {}", path.display());
+                self.recorder = Some(recorder);
+            }
+            Err(err) => error!("[EventLoop::start_recording] {}: {err}", path.display()),
+        }
+        Ok(())
+    }
+
+    /// Runs `f` on the recorder, if any. A recording that fails is dropped
+    /// rather than taking the whole session down with it.
+    fn record<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut Recorder<BufWriter<File>>) -> io::Result<()>,
+    {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(err) = f(recorder) {
+                error!("[EventLoop::record] stopping the recording: {err}");
+                self.recorder = None;
+            }
+        }
+    }
+
+    /// Only ask for writability while there is something left to write,
+    /// otherwise poll would spin on an always writable pty.
+    fn update_interest(&mut self) -> Result<(), PtyError> {
+        if self.hung_up {
+            return Ok(());
+        }
+        let interest = if self.write_queue.is_empty() {
+            Interest::READABLE
+        } else {
+            Interest::READABLE | Interest::WRITABLE
+        };
+        if interest != self.interest {
+            self.poll
+                .registry()
+                .reregister(&mut SourceFd(&self.pty.as_raw_fd()), PTY_TOKEN, interest)
+                .map_err(PtyError::UnableToPoll)?;
+            self.interest = interest;
+        }
+        Ok(())
+    }
+
+    /// Stops listening on the pty, a closed pty would otherwise be reported
+    /// as readable forever. The loop keeps running until the child is reaped,
+    /// unless it is gone already.
+    fn hangup(&mut self) -> Result<Flow, PtyError> {
+        debug!("[EventLoop::hangup] child closed the pty");
+        self.poll
+            .registry()
+            .deregister(&mut SourceFd(&self.pty.as_raw_fd()))
+            .map_err(PtyError::UnableToPoll)?;
+        self.hung_up = true;
+        self.handler.on_hangup();
+
+        // Transports without a child never send SIGCHLD.
+        match self.pty.try_wait()? {
+            Some(status) => {
+                self.handler.on_exit(status);
+                Ok(Flow::Stop)
+            }
+            None => Ok(Flow::Continue),
+        }
+    }
+}
+
+impl<T: Tty, H: Handler> Drop for EventLoop<T, H> {
+    fn drop(&mut self) {
+        signal_hook::low_level::unregister(self.signal_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Pty;
+
+    enum Event {
+        Output(Vec<u8>),
+        Hangup,
+        Exit(ChildStatus),
+    }
+
+    struct Collect(Sender<Event>);
+
+    impl Handler for Collect {
+        fn on_output(&mut self, bytes: &[u8]) {
+            let _ = self.0.send(Event::Output(bytes.to_vec()));
+        }
+
+        fn on_hangup(&mut self) {
+            let _ = self.0.send(Event::Hangup);
+        }
+
+        fn on_exit(&mut self, status: ChildStatus) {
+            let _ = self.0.send(Event::Exit(status));
+        }
+    }
+
+    /// Runs `script` to completion, returning its output and exit status.
+    fn run(script: &str, input: &str) -> (String, ChildStatus) {
+        let pty = Pty::builder().command("/bin/sh", &["-c".into(), script.into()]);
+        let (tx, rx) = mpsc::channel();
+        let (event_loop, notifier) = EventLoop::new(pty.spawn().unwrap(), Collect(tx)).unwrap();
+        let thread = event_loop.spawn().unwrap();
+
+        notifier.notify(input);
+
+        let mut output = vec![];
+        let status = loop {
+            match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+                Event::Output(bytes) => output.extend(bytes),
+                Event::Hangup => (),
+                Event::Exit(status) => break status,
+            }
+        };
+        thread.join().unwrap();
+
+        (String::from_utf8_lossy(&output).into_owned(), status)
+    }
+
+    #[test]
+    fn test_echo_input() {
+        let (output, status) = run("read line; echo got $line", "vterm\r");
+
+        assert!(output.contains("got vterm"));
+        assert_eq!(status, ChildStatus::Exited(0));
+    }
+
+    #[test]
+    fn test_input_after_hangup() {
+        // Closes the tty but lives on for a moment, so the input arrives
+        // between the hangup and the exit.
+        let script = "exec </dev/null >/dev/null 2>&1; sleep 0.3; exit 4";
+        let pty = Pty::builder().command("/bin/sh", &["-c".into(), script.into()]);
+        let (tx, rx) = mpsc::channel();
+        let (event_loop, notifier) = EventLoop::new(pty.spawn().unwrap(), Collect(tx)).unwrap();
+        let thread = event_loop.spawn().unwrap();
+
+        let mut hung_up = false;
+        let status = loop {
+            match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+                Event::Output(_) => (),
+                Event::Hangup => {
+                    hung_up = true;
+                    notifier.notify("too late\r");
+                }
+                Event::Exit(status) => break status,
+            }
+        };
+        thread.join().unwrap();
+
+        assert!(hung_up);
+        assert_eq!(status, ChildStatus::Exited(4));
+    }
+
+    #[test]
+    fn test_exit_status() {
+        assert_eq!(run("exit 3", "").1, ChildStatus::Exited(3));
+        assert_eq!(run("kill -9 $$", "").1, ChildStatus::Signaled(9));
+    }
+}
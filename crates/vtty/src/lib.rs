@@ -1 +1,23 @@
-// TODO(nuii): PTTY
+pub mod asciicast;
+mod environment;
+mod error;
+#[cfg(unix)]
+mod event_loop;
+#[cfg(unix)]
+mod loopback;
+#[cfg(unix)]
+mod process;
+#[cfg(unix)]
+mod pty;
+#[cfg(unix)]
+mod tty;
+
+pub use self::{environment::Environment, error::PtyError};
+#[cfg(unix)]
+pub use self::{
+    event_loop::{EventLoop, Handler, Msg, Notifier},
+    loopback::{Loopback, Remote},
+    process::{Foreground, Process},
+    pty::{ChildStatus, Pty, PtyBuilder, WindowSize},
+    tty::Tty,
+};
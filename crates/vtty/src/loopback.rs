@@ -0,0 +1,182 @@
+use std::{
+    io::{self, Read, Write},
+    os::{
+        fd::{AsRawFd, RawFd},
+        unix::net::UnixStream,
+    },
+    sync::{Arc, Mutex},
+};
+
+use crate::{ChildStatus, PtyError, Tty, WindowSize};
+
+/// What both ends of a loopback share besides the socket.
+#[derive(Debug, Default)]
+struct Shared {
+    window_size: WindowSize,
+    status: Option<ChildStatus>,
+}
+
+/// A [`Tty`] without a child process, connected to a [`Remote`] instead.
+///
+/// Whatever is written to one end can be read from the other, so the event
+/// loop, the emulator and the input pipeline can be driven from a test.
+#[derive(Debug)]
+pub struct Loopback {
+    stream: UnixStream,
+    shared: Arc<Mutex<Shared>>,
+}
+
+/// The end of a [`Loopback`] that stands in for the child.
+#[derive(Debug)]
+pub struct Remote {
+    stream: UnixStream,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Loopback {
+    pub fn pair(window_size: WindowSize) -> Result<(Loopback, Remote), PtyError> {
+        let (local, remote) = UnixStream::pair().map_err(PtyError::UnableToOpenPty)?;
+        let shared = Arc::new(Mutex::new(Shared { window_size, status: None }));
+        let loopback = Loopback { stream: local, shared: shared.clone() };
+
+        Ok((loopback, Remote { stream: remote, shared }))
+    }
+}
+
+impl Remote {
+    /// The window size last set through [`Tty::resize`].
+    pub fn window_size(&self) -> WindowSize {
+        self.shared.lock().unwrap().window_size
+    }
+
+    /// Hangs up with `status`, like a child exiting.
+    pub fn exit(self, status: ChildStatus) {
+        self.shared.lock().unwrap().status = Some(status);
+    }
+}
+
+impl Tty for Loopback {
+    fn set_nonblocking(&self) -> Result<(), PtyError> {
+        self.stream.set_nonblocking(true).map_err(PtyError::UnableToConfigurePty)
+    }
+
+    fn window_size(&self) -> Result<WindowSize, PtyError> {
+        Ok(self.shared.lock().unwrap().window_size)
+    }
+
+    fn resize(&self, window_size: WindowSize) -> Result<(), PtyError> {
+        self.shared.lock().unwrap().window_size = window_size;
+        Ok(())
+    }
+
+    fn try_wait(&mut self) -> Result<Option<ChildStatus>, PtyError> {
+        Ok(self.shared.lock().unwrap().status)
+    }
+}
+
+impl AsRawFd for Loopback {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+impl Read for Loopback {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Loopback {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Read for Remote {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Remote {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+impl Drop for Remote {
+    /// Dropping the remote without [`Remote::exit`] counts as a clean exit.
+    /// The status is in place before the socket closes, so the loop finds it
+    /// as soon as it sees the hangup.
+    fn drop(&mut self) {
+        self.shared.lock().unwrap().status.get_or_insert(ChildStatus::Exited(0));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::mpsc, time::Duration};
+
+    use super::*;
+    use crate::{EventLoop, Handler};
+
+    enum Event {
+        Output(Vec<u8>),
+        Exit(ChildStatus),
+    }
+
+    struct Collect(mpsc::Sender<Event>);
+
+    impl Handler for Collect {
+        fn on_output(&mut self, bytes: &[u8]) {
+            let _ = self.0.send(Event::Output(bytes.to_vec()));
+        }
+
+        fn on_hangup(&mut self) {}
+
+        fn on_exit(&mut self, status: ChildStatus) {
+            let _ = self.0.send(Event::Exit(status));
+        }
+    }
+
+    #[test]
+    fn test_event_loop() {
+        let size = WindowSize { columns: 80, lines: 24, ..Default::default() };
+        let (loopback, mut remote) = Loopback::pair(size).unwrap();
+        let (tx, rx) = mpsc::channel();
+        let (event_loop, notifier) = EventLoop::new(loopback, Collect(tx)).unwrap();
+        let thread = event_loop.spawn().unwrap();
+
+        notifier.notify("ls\r");
+        let mut input = [0; 3];
+        remote.read_exact(&mut input).unwrap();
+        assert_eq!(&input, b"ls\r");
+
+        remote.write_all(b"a  b\r\n").unwrap();
+        match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+            Event::Output(output) => assert_eq!(output, b"a  b\r\n"),
+            Event::Exit(_) => panic!("exited before the output arrived"),
+        }
+
+        let resized = WindowSize { columns: 100, ..size };
+        notifier.resize(resized);
+        notifier.notify("exit\r");
+        remote.read_exact(&mut [0; 5]).unwrap();
+        assert_eq!(remote.window_size(), resized);
+
+        remote.exit(ChildStatus::Exited(3));
+        match rx.recv_timeout(Duration::from_secs(5)).unwrap() {
+            Event::Exit(status) => assert_eq!(status, ChildStatus::Exited(3)),
+            Event::Output(_) => panic!("unexpected output"),
+        }
+        thread.join().unwrap();
+    }
+}
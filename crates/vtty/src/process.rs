@@ -0,0 +1,105 @@
+use std::{fs, path::PathBuf};
+
+/// Looks up what runs in the foreground of a pty.
+///
+/// Goes through the child's `/proc` entry rather than a handle to the master
+/// side, so it can be used from any thread and never keeps the pty open after
+/// the [`Pty`](crate::Pty) is dropped.
+#[derive(Debug, Clone)]
+pub struct Foreground {
+    child: i32,
+}
+
+impl Foreground {
+    pub(crate) fn new(child: i32) -> Self {
+        Self { child }
+    }
+
+    /// The process group currently in the foreground of the pty, `None` once
+    /// the child is gone.
+    pub fn pgid(&self) -> Option<i32> {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", self.child)).ok()?;
+        // The name in parentheses may contain spaces, the fields after it
+        // are state, ppid, pgrp, session, tty_nr and tpgid.
+        let (_, fields) = stat.rsplit_once(')')?;
+        let pgid: i32 = fields.split_whitespace().nth(5)?.parse().ok()?;
+        (pgid > 0).then_some(pgid)
+    }
+
+    /// The leader of the foreground process group, e.g. `vim` while it runs
+    /// and the shell otherwise.
+    pub fn process(&self) -> Option<Process> {
+        Process::from_pid(self.pgid()?)
+    }
+}
+
+/// A snapshot of a running process, read from `/proc`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Process {
+    pub pid: i32,
+    /// The executable name, as shown by `ps`.
+    pub name: String,
+    pub cmdline: Vec<String>,
+    /// Missing when the process belongs to another user.
+    pub cwd: Option<PathBuf>,
+}
+
+impl Process {
+    pub fn from_pid(pid: i32) -> Option<Self> {
+        let proc = PathBuf::from(format!("/proc/{pid}"));
+        let name = fs::read_to_string(proc.join("comm")).ok()?.trim_end().to_string();
+        let cmdline = fs::read(proc.join("cmdline"))
+            .map(|cmdline| {
+                cmdline
+                    .split(|&b| b == 0)
+                    .filter(|arg| !arg.is_empty())
+                    .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let cwd = fs::read_link(proc.join("cwd")).ok();
+
+        Some(Self { pid, name, cmdline, cwd })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        env, thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+    use crate::Pty;
+
+    #[test]
+    fn test_own_process() {
+        let process = Process::from_pid(std::process::id() as i32).unwrap();
+
+        assert_eq!(process.cwd, env::current_dir().ok());
+        assert!(!process.cmdline.is_empty());
+    }
+
+    #[test]
+    fn test_foreground_process() {
+        let pty = Pty::builder()
+            .command("/bin/sh", &["-c".into(), "cd / && exec sleep 5".into()])
+            .spawn()
+            .unwrap();
+        let foreground = pty.foreground().unwrap();
+
+        let start = Instant::now();
+        let process = loop {
+            match foreground.process() {
+                Some(process) if process.name == "sleep" => break process,
+                _ if start.elapsed() > Duration::from_secs(5) => panic!("sleep never started"),
+                _ => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+
+        assert_eq!(process.pid, pty.child_id() as i32);
+        assert_eq!(process.cwd, Some(PathBuf::from("/")));
+        assert_eq!(process.cmdline, ["sleep", "5"]);
+    }
+}
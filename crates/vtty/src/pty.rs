@@ -0,0 +1,534 @@
+use std::{
+    env,
+    ffi::CStr,
+    fmt::{self, Display},
+    fs::File,
+    io::{self, Read, Write},
+    mem::{self, ManuallyDrop},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::process::{CommandExt, ExitStatusExt},
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    ptr, thread,
+    time::{Duration, Instant},
+};
+
+use libc::{c_char, c_int};
+use log::{debug, warn};
+
+use crate::{Environment, Foreground, PtyError, Tty};
+
+/// Used when neither a command, the passwd entry nor `$SHELL` name a shell.
+const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// How long a dropped pty waits for its child after SIGHUP, and then again
+/// after SIGKILL.
+const EXIT_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Configures and spawns a child process attached to a new pseudoterminal.
+#[derive(Debug, Default, Clone)]
+pub struct PtyBuilder {
+    program: Option<String>,
+    args: Vec<String>,
+    window_size: Option<WindowSize>,
+    environment: Option<Environment>,
+    working_directory: Option<PathBuf>,
+    login: bool,
+}
+
+impl PtyBuilder {
+    /// Runs `program` with `args` instead of the user's shell.
+    pub fn command<S: Into<String>>(mut self, program: S, args: &[String]) -> Self {
+        self.program = Some(program.into());
+        self.args = args.to_vec();
+        self
+    }
+
+    /// The size the child sees on startup, before the first resize.
+    pub fn window_size(mut self, window_size: WindowSize) -> Self {
+        self.window_size = Some(window_size);
+        self
+    }
+
+    /// Replaces the environment the child would otherwise inherit from vterm.
+    pub fn environment(mut self, environment: Environment) -> Self {
+        self.environment = Some(environment);
+        self
+    }
+
+    /// Starts the child in `path` instead of vterm's working directory.
+    pub fn working_directory<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.working_directory = Some(path.into());
+        self
+    }
+
+    /// Runs the program as a login shell, by prefixing its `argv[0]` with `-`.
+    pub fn login(mut self, login: bool) -> Self {
+        self.login = login;
+        self
+    }
+
+    /// Opens a master/slave pair, then forks and execs the configured program
+    /// with the slave side as its stdio and controlling terminal.
+    pub fn spawn(self) -> Result<Pty, PtyError> {
+        let (master, slave) = open_pty(self.window_size)?;
+        let program = self.program.unwrap_or_else(default_shell);
+
+        let mut command = Command::new(&program);
+        command
+            .args(&self.args)
+            .stdin(stdio(&slave)?)
+            .stdout(stdio(&slave)?)
+            .stderr(stdio(&slave)?);
+        if let Some(environment) = &self.environment {
+            command.env_clear().envs(environment);
+        }
+        if let Some(working_directory) = &self.working_directory {
+            command.current_dir(working_directory);
+        }
+        if self.login {
+            let name = Path::new(&program).file_name().unwrap_or_default().to_string_lossy();
+            command.arg0(format!("-{name}"));
+        }
+
+        // Runs in the forked child right before exec, after stdio has been
+        // redirected to the slave. Only async-signal-safe calls are allowed here.
+        unsafe {
+            command.pre_exec(|| {
+                // Start a new session so the slave can become our controlling
+                // terminal, which is what makes job control work.
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+
+                // Don't leak vterm's own signal dispositions into the child.
+                for signal in [
+                    libc::SIGCHLD,
+                    libc::SIGHUP,
+                    libc::SIGINT,
+                    libc::SIGQUIT,
+                    libc::SIGTERM,
+                    libc::SIGALRM,
+                ] {
+                    libc::signal(signal, libc::SIG_DFL);
+                }
+
+                Ok(())
+            });
+        }
+
+        let child = command.spawn().map_err(|err| PtyError::UnableToSpawn(program.clone(), err))?;
+        debug!("[Pty::spawn] started `{program}` with pid {}", child.id());
+
+        Ok(Pty { child, file: ManuallyDrop::new(File::from(master)) })
+    }
+}
+
+/// A child process running on the slave side of a pseudoterminal.
+///
+/// Reading and writing goes through the master side, which is owned by this
+/// type. Dropping it hangs up the child and waits for it to exit.
+#[derive(Debug)]
+pub struct Pty {
+    child: Child,
+    /// Closed first when dropped, see [`Pty::drop`].
+    file: ManuallyDrop<File>,
+}
+
+impl Pty {
+    pub fn builder() -> PtyBuilder {
+        PtyBuilder::default()
+    }
+
+    /// The process id of the spawned child.
+    pub fn child_id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// The master side of the pseudoterminal.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Polls until the child exits or `timeout` passes. Errors count as
+    /// gone, there is nothing left to wait for then.
+    fn wait_timeout(&mut self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        loop {
+            match self.child.try_wait() {
+                Ok(None) if start.elapsed() < timeout => thread::sleep(Duration::from_millis(5)),
+                Ok(None) => return false,
+                Ok(Some(_)) | Err(_) => return true,
+            }
+        }
+    }
+
+    /// A handle for looking up the foreground process, e.g. for the title.
+    pub fn foreground(&self) -> Result<Foreground, PtyError> {
+        Ok(Foreground::new(self.child.id() as i32))
+    }
+
+    /// Reaps the child if it has exited, without blocking.
+    pub fn try_wait(&mut self) -> Result<Option<ChildStatus>, PtyError> {
+        let status = self.child.try_wait().map_err(PtyError::UnableToWait)?;
+        Ok(status.map(ChildStatus::from))
+    }
+
+    /// The window size the child currently sees.
+    pub fn window_size(&self) -> Result<WindowSize, PtyError> {
+        let mut winsize: libc::winsize = unsafe { mem::zeroed() };
+        let result = unsafe { libc::ioctl(self.file.as_raw_fd(), libc::TIOCGWINSZ, &mut winsize) };
+        if result == -1 {
+            return Err(PtyError::UnableToConfigurePty(io::Error::last_os_error()));
+        }
+        Ok(WindowSize::from_winsize(winsize))
+    }
+
+    /// Tells the child about a new window size. The kernel follows up with a
+    /// SIGWINCH to the foreground process group.
+    pub fn resize(&self, window_size: WindowSize) -> Result<(), PtyError> {
+        let winsize = window_size.to_winsize();
+        let result = unsafe { libc::ioctl(self.file.as_raw_fd(), libc::TIOCSWINSZ, &winsize) };
+        if result == -1 {
+            return Err(PtyError::UnableToResize(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+}
+
+/// The dimensions of the terminal, in cells and in pixels per cell.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WindowSize {
+    pub columns: u16,
+    pub lines: u16,
+    pub cell_width: u16,
+    pub cell_height: u16,
+}
+
+impl WindowSize {
+    fn from_winsize(winsize: libc::winsize) -> Self {
+        Self {
+            columns: winsize.ws_col,
+            lines: winsize.ws_row,
+            cell_width: winsize.ws_xpixel.checked_div(winsize.ws_col).unwrap_or_default(),
+            cell_height: winsize.ws_ypixel.checked_div(winsize.ws_row).unwrap_or_default(),
+        }
+    }
+
+    fn to_winsize(self) -> libc::winsize {
+        libc::winsize {
+            ws_row: self.lines,
+            ws_col: self.columns,
+            ws_xpixel: self.columns.saturating_mul(self.cell_width),
+            ws_ypixel: self.lines.saturating_mul(self.cell_height),
+        }
+    }
+}
+
+/// How the child process ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildStatus {
+    /// The child exited on its own with this status code.
+    Exited(i32),
+    /// The child was terminated by this signal.
+    Signaled(i32),
+}
+
+impl From<ExitStatus> for ChildStatus {
+    fn from(status: ExitStatus) -> Self {
+        match status.code() {
+            Some(code) => Self::Exited(code),
+            None => Self::Signaled(status.signal().unwrap_or_default()),
+        }
+    }
+}
+
+impl Display for ChildStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exited(code) => write!(f, "exited with status {code}"),
+            Self::Signaled(signal) => write!(f, "was killed by signal {signal}"),
+        }
+    }
+}
+
+impl Tty for Pty {
+    fn set_nonblocking(&self) -> Result<(), PtyError> {
+        let fd = self.file.as_raw_fd();
+        let result = unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK)
+        };
+        if result == -1 {
+            return Err(PtyError::UnableToConfigurePty(io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    fn window_size(&self) -> Result<WindowSize, PtyError> {
+        Pty::window_size(self)
+    }
+
+    fn resize(&self, window_size: WindowSize) -> Result<(), PtyError> {
+        Pty::resize(self, window_size)
+    }
+
+    fn try_wait(&mut self) -> Result<Option<ChildStatus>, PtyError> {
+        Pty::try_wait(self)
+    }
+}
+
+impl AsRawFd for Pty {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl Read for Pty {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Write for Pty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for Pty {
+    /// Hangs up on the child and reaps it. Waits at most twice
+    /// [`EXIT_TIMEOUT`], a child that ignores SIGHUP gets killed and one
+    /// stuck even then is reaped in the background.
+    fn drop(&mut self) {
+        // Closing the master hangs up the slave side, nothing else holds it
+        // open, and a child that keeps writing can't block on output nobody
+        // reads anymore.
+        unsafe { ManuallyDrop::drop(&mut self.file) };
+
+        // Once reaped, the pid may already belong to some other process.
+        if let Ok(Some(_)) = self.child.try_wait() {
+            return;
+        }
+        let pid = self.child.id() as c_int;
+        unsafe {
+            libc::kill(pid, libc::SIGHUP);
+        }
+        if self.wait_timeout(EXIT_TIMEOUT) {
+            return;
+        }
+
+        warn!("[Pty::drop] child {pid} ignored SIGHUP, killing it");
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+        if self.wait_timeout(EXIT_TIMEOUT) {
+            return;
+        }
+
+        // Stuck in the kernel, it's reaped whenever it gets out.
+        warn!("[Pty::drop] child {pid} is still alive, reaping it in the background");
+        let reaper = thread::Builder::new().name("pty reaper".into()).spawn(move || unsafe {
+            libc::waitpid(pid, ptr::null_mut(), 0);
+        });
+        if let Err(err) = reaper {
+            warn!("[Pty::drop] unable to spawn a reaper thread: {err}");
+        }
+    }
+}
+
+/// The user's shell from the passwd entry, falling back to `$SHELL` and
+/// then [`DEFAULT_SHELL`].
+fn default_shell() -> String {
+    pick_shell(passwd_shell(), env::var("SHELL").ok())
+}
+
+fn pick_shell(passwd: Option<String>, env: Option<String>) -> String {
+    passwd.or_else(|| env.filter(|shell| !shell.is_empty())).unwrap_or_else(|| DEFAULT_SHELL.into())
+}
+
+fn passwd_shell() -> Option<String> {
+    let mut buf: Vec<c_char> = vec![0; 1024];
+    loop {
+        let mut passwd: libc::passwd = unsafe { mem::zeroed() };
+        let mut entry = ptr::null_mut();
+        let result = unsafe {
+            libc::getpwuid_r(libc::getuid(), &mut passwd, buf.as_mut_ptr(), buf.len(), &mut entry)
+        };
+
+        if result == libc::ERANGE {
+            buf.resize(buf.len() * 2, 0);
+            continue;
+        }
+        if result != 0 || entry.is_null() || passwd.pw_shell.is_null() {
+            return None;
+        }
+
+        let shell = unsafe { CStr::from_ptr(passwd.pw_shell) };
+        return shell.to_str().ok().filter(|shell| !shell.is_empty()).map(String::from);
+    }
+}
+
+fn open_pty(window_size: Option<WindowSize>) -> Result<(OwnedFd, OwnedFd), PtyError> {
+    let mut master: c_int = -1;
+    let mut slave: c_int = -1;
+
+    let winsize = window_size.map(WindowSize::to_winsize);
+    let winp = winsize.as_ref().map_or(ptr::null(), |winsize| winsize as *const _);
+    let result =
+        unsafe { libc::openpty(&mut master, &mut slave, ptr::null_mut(), ptr::null(), winp) };
+    if result == -1 {
+        return Err(PtyError::UnableToOpenPty(io::Error::last_os_error()));
+    }
+    let (master, slave) = unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) };
+
+    // Only the stdio copies of the slave should end up in the child.
+    set_cloexec(&master)?;
+    set_cloexec(&slave)?;
+
+    Ok((master, slave))
+}
+
+fn set_cloexec(fd: &OwnedFd) -> Result<(), PtyError> {
+    let result = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) };
+    if result == -1 {
+        return Err(PtyError::UnableToConfigurePty(io::Error::last_os_error()));
+    }
+    Ok(())
+}
+
+fn stdio(slave: &OwnedFd) -> Result<Stdio, PtyError> {
+    slave.try_clone().map(Stdio::from).map_err(PtyError::UnableToConfigurePty)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Reads from the master until the child hangs up.
+    fn read_to_end(pty: &mut Pty) -> String {
+        let mut output = vec![];
+        let mut buf = [0; 1024];
+        loop {
+            match pty.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => output.extend_from_slice(&buf[.. n]),
+                // Linux reports EIO once the slave side is closed.
+                Err(_) => break,
+            }
+        }
+        String::from_utf8_lossy(&output).into_owned()
+    }
+
+    #[test]
+    fn test_spawn_command() {
+        let mut pty = Pty::builder()
+            .command("/bin/sh", &["-c".into(), "echo hello from vtty".into()])
+            .spawn()
+            .unwrap();
+
+        assert!(read_to_end(&mut pty).contains("hello from vtty"));
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut pty = Pty::builder()
+            .command("/bin/sh", &["-c".into(), "sleep 0.2; stty size".into()])
+            .window_size(WindowSize { columns: 80, lines: 24, cell_width: 8, cell_height: 16 })
+            .spawn()
+            .unwrap();
+        pty.resize(WindowSize { columns: 132, lines: 43, cell_width: 8, cell_height: 16 }).unwrap();
+
+        assert!(read_to_end(&mut pty).contains("43 132"));
+    }
+
+    #[test]
+    fn test_environment() {
+        let environment =
+            Environment::inherit().set("VTTY_TEST", "set for the child").remove("HOME");
+        let mut pty = Pty::builder()
+            .command("/bin/sh", &["-c".into(), "echo $VTTY_TEST ${HOME-unset}".into()])
+            .environment(environment)
+            .spawn()
+            .unwrap();
+
+        assert!(read_to_end(&mut pty).contains("set for the child unset"));
+        assert!(env::var_os("VTTY_TEST").is_none());
+    }
+
+    #[test]
+    fn test_default_shell() {
+        let some = |shell: &str| Some(shell.to_string());
+        assert_eq!(pick_shell(some("/bin/zsh"), some("/bin/bash")), "/bin/zsh");
+        assert_eq!(pick_shell(None, some("/bin/bash")), "/bin/bash");
+        assert_eq!(pick_shell(None, some("")), DEFAULT_SHELL);
+        assert_eq!(pick_shell(None, None), DEFAULT_SHELL);
+
+        if let Some(shell) = passwd_shell() {
+            assert_eq!(default_shell(), shell);
+        }
+    }
+
+    #[test]
+    fn test_login_shell() {
+        let mut pty = Pty::builder()
+            .command("/bin/sh", &["-c".into(), "echo argv0=$0".into()])
+            .login(true)
+            .spawn()
+            .unwrap();
+
+        assert!(read_to_end(&mut pty).contains("argv0=-sh"));
+    }
+
+    #[test]
+    fn test_working_directory() {
+        let mut pty = Pty::builder()
+            .command("/bin/sh", &["-c".into(), "echo cwd=$(pwd)".into()])
+            .working_directory("/")
+            .spawn()
+            .unwrap();
+
+        assert!(read_to_end(&mut pty).contains("cwd=/\r"));
+    }
+
+    #[test]
+    fn test_drop_kills_child_ignoring_sighup() {
+        let pty = Pty::builder()
+            .command("/bin/sh", &["-c".into(), "trap '' HUP; echo ready; sleep 30".into()])
+            .spawn()
+            .unwrap();
+        let mut output = vec![];
+        let mut buf = [0; 64];
+        while !String::from_utf8_lossy(&output).contains("ready") {
+            let n = pty.file().read(&mut buf).unwrap();
+            output.extend_from_slice(&buf[.. n]);
+        }
+        let pid = pty.child_id() as c_int;
+
+        let start = Instant::now();
+        drop(pty);
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // Reaped, so the pid no longer exists.
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
+    }
+
+    #[test]
+    fn test_controlling_terminal() {
+        let mut pty = Pty::builder()
+            .command("/bin/sh", &["-c".into(), "test -t 0 && tty".into()])
+            .spawn()
+            .unwrap();
+
+        assert!(read_to_end(&mut pty).contains("/dev/pts/"));
+    }
+}
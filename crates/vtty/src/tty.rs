@@ -0,0 +1,25 @@
+use std::{
+    io::{Read, Write},
+    os::fd::AsRawFd,
+};
+
+use crate::{ChildStatus, PtyError, WindowSize};
+
+/// A transport between the terminal and whatever runs behind it.
+///
+/// Output is read and input written through [`Read`] and [`Write`]. The file
+/// descriptor is polled by the [`EventLoop`](crate::EventLoop), so it has to
+/// support non-blocking I/O.
+pub trait Tty: Read + Write + AsRawFd + Send + 'static {
+    /// Switches to non-blocking mode for use with a poller.
+    fn set_nonblocking(&self) -> Result<(), PtyError>;
+
+    /// The window size the other side currently sees.
+    fn window_size(&self) -> Result<WindowSize, PtyError>;
+
+    /// Tells the other side about a new window size.
+    fn resize(&self, window_size: WindowSize) -> Result<(), PtyError>;
+
+    /// The exit status of the other side once it is gone, without blocking.
+    fn try_wait(&mut self) -> Result<Option<ChildStatus>, PtyError>;
+}
@@ -249,6 +249,11 @@ impl Font {
     pub fn line_height(&self) -> f32 {
         self.font.height()
     }
+
+    /// Horizontal advance of a single character, without kerning.
+    pub fn h_advance(&self, c: char) -> f32 {
+        self.font.h_advance(self.font.glyph_id(c))
+    }
 }
 
 impl From<ab_glyph::Rect> for Rect {